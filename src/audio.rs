@@ -0,0 +1,139 @@
+use std::io;
+
+/// Frames per second at which the sound timer is decremented.
+const FRAME_RATE: u64 = 60;
+
+/// Amplitude of the square wave, leaving some headroom below full scale.
+const AMPLITUDE: i16 = i16::MAX / 4;
+
+/// [Buzzer] renders the state of the Chip-8 buzzer into a mono square wave, one 60 Hz
+/// frame at a time.
+pub struct Buzzer {
+    sample_rate: u32,
+    pitch: u32,
+    frames: u64,
+    samples: Vec<i16>,
+}
+
+impl Buzzer {
+    /// Constructs a buzzer producing a 440 Hz tone at 44.1 kHz.
+    pub fn new() -> Self {
+        Buzzer {
+            sample_rate: 44_100,
+            pitch: 440,
+            frames: 0,
+            samples: Vec::new(),
+        }
+    }
+
+    /// Sets the number of samples rendered per second.
+    pub fn with_sample_rate(self, sample_rate: u32) -> Self {
+        Buzzer {
+            sample_rate: sample_rate.max(1),
+            ..self
+        }
+    }
+
+    /// Sets the frequency in Hz of the tone played while the buzzer is sounding.
+    pub fn with_pitch(self, pitch: u32) -> Self {
+        Buzzer {
+            pitch: pitch.max(1),
+            ..self
+        }
+    }
+
+    /// Renders the samples covering the next frame, either a tone or silence. The number
+    /// of samples per frame varies so that the total stays in step with the frame count.
+    pub fn frame(&mut self, sounding: bool) {
+        self.frames += 1;
+        let end = (self.frames * self.sample_rate as u64 / FRAME_RATE) as usize;
+        while self.samples.len() < end {
+            let sample = if sounding {
+                // The phase is derived from the sample position so that the wave stays
+                // continuous across consecutive frames.
+                let half_periods = self.samples.len() as u64 * self.pitch as u64 * 2;
+                if (half_periods / self.sample_rate as u64).is_multiple_of(2) {
+                    AMPLITUDE
+                } else {
+                    -AMPLITUDE
+                }
+            } else {
+                0
+            };
+
+            self.samples.push(sample);
+        }
+    }
+
+    /// Writes the rendered samples as a 16-bit mono PCM WAV file.
+    pub fn write_wav<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        let data_len = (self.samples.len() * 2) as u32;
+        let byte_rate = self.sample_rate * 2;
+
+        w.write_all(b"RIFF")?;
+        w.write_all(&(36 + data_len).to_le_bytes())?;
+        w.write_all(b"WAVE")?;
+
+        // Format chunk: PCM, one channel, two bytes per sample.
+        w.write_all(b"fmt ")?;
+        w.write_all(&16u32.to_le_bytes())?;
+        w.write_all(&1u16.to_le_bytes())?;
+        w.write_all(&1u16.to_le_bytes())?;
+        w.write_all(&self.sample_rate.to_le_bytes())?;
+        w.write_all(&byte_rate.to_le_bytes())?;
+        w.write_all(&2u16.to_le_bytes())?;
+        w.write_all(&16u16.to_le_bytes())?;
+
+        w.write_all(b"data")?;
+        w.write_all(&data_len.to_le_bytes())?;
+        let data: Vec<u8> = self.samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        w.write_all(&data)
+    }
+}
+
+impl Default for Buzzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emulation::Emulator;
+
+    #[test]
+    fn square_wave() {
+        let mut buzzer = Buzzer::new().with_sample_rate(8000).with_pitch(1000);
+        buzzer.frame(true);
+        buzzer.frame(false);
+
+        let (high, low) = (AMPLITUDE, -AMPLITUDE);
+        let samples = &buzzer.samples;
+        assert_eq!(samples.len(), 266);
+        assert_eq!(samples[..8], [high, high, high, high, low, low, low, low]);
+        assert!(samples[133..].iter().all(|&s| s == 0));
+
+        for _ in 0..58 {
+            buzzer.frame(false);
+        }
+
+        assert_eq!(buzzer.samples.len(), 8000);
+    }
+
+    #[test]
+    fn sound_timer_drives_buzzer() {
+        // LD V0, 0x03; LD ST, V0; JP 0x204
+        let program = [0x60, 0x03, 0xF0, 0x18, 0x12, 0x04];
+        let mut emulator = Emulator::new();
+        emulator.load(&program).unwrap();
+
+        let mut sounding = Vec::new();
+        for _ in 0..5 {
+            emulator.run_frame().unwrap();
+            sounding.push(emulator.buzzer());
+        }
+
+        assert_eq!(sounding, [true, true, true, false, false]);
+    }
+}
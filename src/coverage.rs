@@ -0,0 +1,334 @@
+use std::{collections::BTreeMap, io};
+
+use crate::{
+    data::Addr,
+    emulation::{EmulationError, Emulator, START_ADDRESS},
+    lint::Linter,
+    opcode::{Flow, Opcode},
+};
+
+/// [Coverage] records which instructions of a program were executed and which way each
+/// skip instruction went, accumulating over any number of runs.
+#[derive(Clone, Debug, Default)]
+pub struct Coverage {
+    executed: BTreeMap<Addr, u64>,
+    branches: BTreeMap<Addr, Branches>,
+}
+
+/// [Branches] counts the outcomes of a skip instruction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Branches {
+    /// Number of times the next instruction was skipped.
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+impl Coverage {
+    /// Constructs an empty coverage record.
+    pub fn new() -> Self {
+        Coverage::default()
+    }
+
+    /// Executes a single instruction on `emulator`, recording it.
+    pub fn step(&mut self, emulator: &mut Emulator) -> Result<Opcode, EmulationError> {
+        let pc = emulator.program_counter();
+        let opcode = emulator.step()?;
+
+        *self.executed.entry(pc).or_insert(0) += 1;
+        if opcode.flow() == Flow::Skip {
+            let branches = self.branches.entry(pc).or_default();
+            if emulator.program_counter() == pc.wrapping_add(4) {
+                branches.taken += 1;
+            } else {
+                branches.not_taken += 1;
+            }
+        }
+
+        Ok(opcode)
+    }
+
+    /// Returns the number of executions of each address.
+    pub fn executed(&self) -> &BTreeMap<Addr, u64> {
+        &self.executed
+    }
+
+    /// Returns the outcomes of each skip instruction executed.
+    pub fn branches(&self) -> &BTreeMap<Addr, Branches> {
+        &self.branches
+    }
+
+    /// Writes a disassembly of `program` annotated with execution counts, in the style
+    /// of `gcov`: instructions which were reachable but never executed are marked with
+    /// `#####` and words which are not reachable as code with `-`. Skip instructions
+    /// show how often they skipped.
+    pub fn write_listing<W: io::Write>(&self, program: &[u8], w: &mut W) -> io::Result<()> {
+        let reachable = Linter::new().reachability(program).instructions;
+
+        for (i, bytes) in program.chunks_exact(2).enumerate() {
+            let address = START_ADDRESS + 2 * i as Addr;
+            let count = match self.executed.get(&address) {
+                Some(count) => count.to_string(),
+                None if reachable.contains(&address) => String::from("#####"),
+                None => String::from("-"),
+            };
+
+            let text = match Opcode::decode(bytes) {
+                Some(opcode) => opcode.to_string(),
+                None => String::from("--"),
+            };
+
+            write!(w, "{:>10}  {:03X}  {}", count, address, text)?;
+            if let Some(branches) = self.branches.get(&address) {
+                write!(
+                    w,
+                    "  ; skipped {}, not skipped {}",
+                    branches.taken, branches.not_taken
+                )?;
+            }
+
+            writeln!(w)?;
+        }
+
+        let executed = reachable
+            .iter()
+            .filter(|a| self.executed.contains_key(a))
+            .count();
+        let branches = self.branches.values();
+        let outcomes: usize = branches
+            .map(|b| (b.taken > 0) as usize + (b.not_taken > 0) as usize)
+            .sum();
+        let skips = reachable.iter().filter(|&&a| is_skip(program, a)).count();
+
+        writeln!(
+            w,
+            "\n{} of {} reachable instructions executed ({:.1}%), {} of {} branches taken",
+            executed,
+            reachable.len(),
+            percent(executed, reachable.len()),
+            outcomes,
+            2 * skips
+        )
+    }
+
+    /// Writes the coverage as an LCOV tracefile, attributing instructions of `program`
+    /// to source lines through `source_map`. Addresses missing from the map are left out.
+    /// Skip instructions which never ran are reported as branches with unknown outcomes.
+    pub fn write_lcov<W: io::Write>(
+        &self,
+        program: &[u8],
+        source_map: &SourceMap,
+        w: &mut W,
+    ) -> io::Result<()> {
+        let mut files: BTreeMap<&str, BTreeMap<u32, Vec<Addr>>> = BTreeMap::new();
+        for (&address, (file, line)) in &source_map.lines {
+            let lines = files.entry(file.as_str()).or_default();
+            lines.entry(*line).or_default().push(address);
+        }
+
+        writeln!(w, "TN:")?;
+        for (file, lines) in files {
+            writeln!(w, "SF:{}", file)?;
+
+            let (mut found, mut hit) = (0, 0);
+            let (mut branches_found, mut branches_hit) = (0, 0);
+            for (line, addresses) in &lines {
+                let executed = addresses.iter().filter_map(|a| self.executed.get(a));
+                let count = executed.max().copied().unwrap_or(0);
+                for (block, address) in addresses.iter().enumerate() {
+                    let outcomes = match self.branches.get(address) {
+                        Some(b) => [Some(b.taken), Some(b.not_taken)],
+                        None if is_skip(program, *address) => [None, None],
+                        None => continue,
+                    };
+
+                    for (branch, taken) in outcomes.iter().enumerate() {
+                        let taken = taken.map_or(String::from("-"), |t| t.to_string());
+                        writeln!(w, "BRDA:{},{},{},{}", line, block, branch, taken)?;
+                    }
+
+                    branches_found += 2;
+                    branches_hit += outcomes.iter().filter(|t| t.unwrap_or(0) > 0).count();
+                }
+
+                writeln!(w, "DA:{},{}", line, count)?;
+                found += 1;
+                hit += (count > 0) as usize;
+            }
+
+            writeln!(w, "BRF:{}", branches_found)?;
+            writeln!(w, "BRH:{}", branches_hit)?;
+            writeln!(w, "LF:{}", found)?;
+            writeln!(w, "LH:{}", hit)?;
+            writeln!(w, "end_of_record")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns true if the instruction of `program` at `address` is a skip instruction.
+fn is_skip(program: &[u8], address: Addr) -> bool {
+    let offset = address.wrapping_sub(START_ADDRESS) as usize;
+    let opcode = program.get(offset..offset + 2).and_then(Opcode::decode);
+    opcode.map(|o| o.flow()) == Some(Flow::Skip)
+}
+
+fn percent(part: usize, total: usize) -> f64 {
+    100.0 * part as f64 / total.max(1) as f64
+}
+
+/// [SourceMap] maps the addresses of assembled instructions back to the source lines
+/// they came from.
+///
+/// Source maps are text files with one instruction per line: its hexadecimal address
+/// followed by the source file and line number. Blank lines and lines starting with `#`
+/// are ignored:
+///
+/// ```text
+/// # address file:line
+/// 200 game.8o:12
+/// 202 game.8o:13
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    lines: BTreeMap<Addr, (String, u32)>,
+}
+
+impl SourceMap {
+    /// Parses a source map.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = BTreeMap::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let err = |message: &str| format!("line {}: {}", i + 1, message);
+            let (address, location) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| err("expected an address and a source location"))?;
+            let address = u16::from_str_radix(address.trim_start_matches("0x"), 16)
+                .map_err(|_| err("invalid address"))?;
+            let (file, number) = location
+                .trim()
+                .rsplit_once(':')
+                .ok_or_else(|| err("expected file:line"))?;
+            let number = number.parse().map_err(|_| err("invalid line number"))?;
+
+            lines.insert(address, (String::from(file), number));
+        }
+
+        Ok(SourceMap { lines })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 200: LD V0, 0x02
+    // 202: ADD V0, 0xFF
+    // 204: SE V0, 0x00
+    // 206: JP 0x202
+    // 208: JP 0x208
+    // 20A: CLS
+    const PROGRAM: [u8; 12] = [
+        0x60, 0x02, 0x70, 0xFF, 0x30, 0x00, 0x12, 0x02, 0x12, 0x08, 0x00, 0xE0,
+    ];
+
+    fn coverage() -> Coverage {
+        let mut emulator = Emulator::new();
+        emulator.load(&PROGRAM).unwrap();
+
+        let mut coverage = Coverage::new();
+        for _ in 0..10 {
+            coverage.step(&mut emulator).unwrap();
+        }
+
+        coverage
+    }
+
+    #[test]
+    fn listing() {
+        let coverage = coverage();
+        assert_eq!(coverage.executed()[&0x208], 4);
+        assert_eq!(
+            coverage.branches()[&0x204],
+            Branches {
+                taken: 1,
+                not_taken: 1
+            }
+        );
+
+        let mut out = Vec::new();
+        coverage.write_listing(&PROGRAM, &mut out).unwrap();
+        let expected = [
+            "         1  200  LD   V0, 0x02",
+            "         2  202  ADD  V0, 0xFF",
+            "         2  204  SE   V0, 0x00  ; skipped 1, not skipped 1",
+            "         1  206  JP   0x202",
+            "         4  208  JP   0x208",
+            "         -  20A  CLS",
+            "",
+            "5 of 5 reachable instructions executed (100.0%), 2 of 2 branches taken",
+            "",
+        ];
+        assert_eq!(String::from_utf8(out).unwrap(), expected.join("\n"));
+    }
+
+    #[test]
+    fn lcov() {
+        let source_map = SourceMap::parse(
+            "# address file:line\n200 game.8o:1\n202 game.8o:3\n204 game.8o:4\n20A game.8o:9\n",
+        )
+        .unwrap();
+        assert!(SourceMap::parse("200 game.8o").is_err());
+
+        let mut out = Vec::new();
+        coverage()
+            .write_lcov(&PROGRAM, &source_map, &mut out)
+            .unwrap();
+        let expected = "\
+TN:
+SF:game.8o
+DA:1,1
+DA:3,2
+BRDA:4,0,0,1
+BRDA:4,0,1,1
+DA:4,2
+DA:9,0
+BRF:2
+BRH:2
+LF:4
+LH:3
+end_of_record
+";
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    #[test]
+    fn lcov_unexecuted_skip() {
+        let source_map = SourceMap::parse("202 game.8o:3\n204 game.8o:4\n").unwrap();
+
+        let mut out = Vec::new();
+        let coverage = Coverage::new();
+        coverage
+            .write_lcov(&PROGRAM, &source_map, &mut out)
+            .unwrap();
+        let expected = "\
+TN:
+SF:game.8o
+DA:3,0
+BRDA:4,0,0,-
+BRDA:4,0,1,-
+DA:4,0
+BRF:2
+BRH:0
+LF:2
+LH:0
+end_of_record
+";
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+}
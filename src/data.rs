@@ -1,4 +1,4 @@
-use std::fmt::{self, Debug, Display, Formatter};
+use core::fmt::{self, Debug, Display, Formatter};
 
 /// 4-bit unsigned integer.
 #[repr(transparent)]
@@ -55,5 +55,13 @@ impl Display for Nibble {
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Register(pub Nibble);
 
+impl Register {
+    /// The first general purpose register V0, used as an offset by `JP V0, nnn`.
+    pub const V0: Register = Register(Nibble(0x0));
+
+    /// The flag register VF.
+    pub const VF: Register = Register(Nibble(0xF));
+}
+
 /// 12-bit unsigned integer representing a memory address.
 pub type Addr = u16;
@@ -0,0 +1,365 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    fs, io,
+    path::Path,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    checksum::sha1,
+    emulation::Emulator,
+    image::Palette,
+    quirks::{Platform, Quirks},
+};
+
+/// [Database] maps the SHA-1 of known ROMs to their metadata and the settings they need
+/// to run correctly.
+///
+/// Databases are read from the `programs.json` format of the community CHIP-8 database:
+/// an array of programs, each with a title, authors and a `roms` object keyed by the
+/// SHA-1 of every known release:
+///
+/// ```json
+/// [
+///   {
+///     "title": "Example",
+///     "authors": ["Someone"],
+///     "roms": {
+///       "da39a3ee5e6b4b0d3255bfef95601890afd80709": {
+///         "platforms": ["superchip"],
+///         "quirkyPlatforms": { "superchip": { "shift": false } },
+///         "tickrate": 30,
+///         "keys": { "up": 5, "down": 8 },
+///         "colors": { "pixels": ["#000000", "#ffcc00"] }
+///       }
+///     }
+///   }
+/// ]
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Database {
+    roms: BTreeMap<String, RomInfo>,
+}
+
+/// [RomInfo] is the metadata of a single ROM in the [Database].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RomInfo {
+    pub title: String,
+    pub authors: Vec<String>,
+
+    /// First platform the ROM is known to run on which has a quirks preset.
+    pub platform: Option<Platform>,
+
+    /// Quirks of `platform` with any ROM specific differences applied.
+    pub quirks: Option<Quirks>,
+
+    pub tick_rate: Option<u32>,
+
+    /// Chip-8 keys used for named inputs such as `up` or `a`.
+    pub keys: BTreeMap<String, u8>,
+
+    /// Colors of unlit and lit pixels.
+    pub palette: Option<Palette>,
+}
+
+impl RomInfo {
+    /// Applies the platform, quirks and tick rate known for this ROM to an emulator. The
+    /// key mapping and palette are left to the frontend.
+    pub fn configure(&self, mut emulator: Emulator) -> Emulator {
+        if let Some(platform) = self.platform {
+            emulator = emulator.with_stack_depth(platform.stack_depth());
+        }
+
+        if let Some(quirks) = self.quirks {
+            emulator = emulator.with_quirks(quirks);
+        }
+
+        if let Some(tick_rate) = self.tick_rate {
+            emulator = emulator.with_tick_rate(tick_rate);
+        }
+
+        emulator
+    }
+
+    /// Writes the known details of the ROM as text, one per line.
+    pub fn write_report<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "title: {}", self.title)?;
+        if !self.authors.is_empty() {
+            writeln!(w, "authors: {}", self.authors.join(", "))?;
+        }
+
+        if let Some(platform) = self.platform {
+            writeln!(w, "platform: {}", platform)?;
+        }
+
+        if let Some(quirks) = self.quirks {
+            let names: Vec<&str> = Quirks::NAMES
+                .into_iter()
+                .zip(quirks.flags())
+                .filter(|(_, set)| *set)
+                .map(|(name, _)| name)
+                .collect();
+            writeln!(w, "quirks: {}", names.join(" "))?;
+        }
+
+        if let Some(tick_rate) = self.tick_rate {
+            writeln!(w, "tick rate: {}", tick_rate)?;
+        }
+
+        if !self.keys.is_empty() {
+            let keys: Vec<String> = self
+                .keys
+                .iter()
+                .map(|(name, key)| format!("{}={:X}", name, key))
+                .collect();
+            writeln!(w, "keys: {}", keys.join(" "))?;
+        }
+
+        if let Some(palette) = self.palette {
+            writeln!(
+                w,
+                "colors: {} on {}",
+                palette.foreground, palette.background
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Database {
+    /// Reads a database from a `programs.json` file.
+    pub fn read(path: &Path) -> Result<Self, DatabaseError> {
+        Database::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Parses a database in the `programs.json` format. Fields which are not used by the
+    /// emulator are ignored, as are platforms it does not emulate.
+    pub fn parse(json: &str) -> Result<Self, DatabaseError> {
+        let programs: Vec<Program> =
+            serde_json::from_str(json).map_err(|err| DatabaseError::Parse(err.to_string()))?;
+
+        let mut roms = BTreeMap::new();
+        for program in programs {
+            for (hash, rom) in program.roms {
+                let platform = rom
+                    .platforms
+                    .iter()
+                    .find_map(|id| preset(id).map(|preset| (id, preset)));
+
+                let quirks = platform.map(|(id, (_, quirks))| {
+                    let overrides = rom.quirky_platforms.get(id.as_str());
+                    overrides.map_or(quirks, |overrides| apply(quirks, overrides))
+                });
+
+                let palette = rom
+                    .colors
+                    .and_then(|colors| match colors.pixels.as_slice() {
+                        [background, foreground, ..] => Some(Palette {
+                            foreground: foreground.parse().ok()?,
+                            background: background.parse().ok()?,
+                        }),
+                        _ => None,
+                    });
+
+                let info = RomInfo {
+                    title: program.title.clone(),
+                    authors: program.authors.clone(),
+                    platform: platform.map(|(_, (platform, _))| platform),
+                    quirks,
+                    tick_rate: rom.tickrate,
+                    keys: rom.keys,
+                    palette,
+                };
+
+                roms.insert(hash.to_ascii_lowercase(), info);
+            }
+        }
+
+        Ok(Database { roms })
+    }
+
+    /// Looks up a ROM by its contents.
+    pub fn lookup(&self, rom: &[u8]) -> Option<&RomInfo> {
+        self.roms.get(&sha1_hex(rom))
+    }
+
+    /// Returns the number of ROMs in the database.
+    pub fn len(&self) -> usize {
+        self.roms.len()
+    }
+
+    /// Returns true if the database holds no ROMs.
+    pub fn is_empty(&self) -> bool {
+        self.roms.is_empty()
+    }
+}
+
+/// Returns the SHA-1 of a ROM as lowercase hexadecimal digits, as used for keys in the
+/// database.
+pub fn sha1_hex(rom: &[u8]) -> String {
+    sha1(rom).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Maps a platform identifier of the community database onto the closest preset.
+fn preset(id: &str) -> Option<(Platform, Quirks)> {
+    let platform = match id {
+        "originalChip8" | "hybridVIP" | "chip8x" | "modernChip8" => Platform::Vip,
+        "chip48" | "superchip1" | "superchip" | "megachip8" => Platform::Schip,
+        "xochip" => Platform::XoChip,
+        _ => return None,
+    };
+
+    let mut quirks = platform.quirks();
+    if id == "modernChip8" {
+        quirks.vf_reset = false;
+        quirks.display_wait = false;
+    }
+
+    Some((platform, quirks))
+}
+
+/// Applies the quirk overrides of a ROM, named as in the community database.
+fn apply(mut quirks: Quirks, overrides: &BTreeMap<String, bool>) -> Quirks {
+    for (name, &set) in overrides {
+        match name.as_str() {
+            "shift" => quirks.shift_uses_vy = !set,
+            "memoryLeaveIUnchanged" => quirks.memory_increments_i = !set,
+            "wrap" => quirks.clip_sprites = !set,
+            "jump" => quirks.jump_uses_vx = set,
+            "vblank" => quirks.display_wait = set,
+            "logic" => quirks.vf_reset = set,
+            _ => {}
+        }
+    }
+
+    quirks
+}
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    #[serde(default)]
+    roms: BTreeMap<String, Rom>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rom {
+    #[serde(default)]
+    platforms: Vec<String>,
+    #[serde(default)]
+    quirky_platforms: BTreeMap<String, BTreeMap<String, bool>>,
+    tickrate: Option<u32>,
+    #[serde(default)]
+    keys: BTreeMap<String, u8>,
+    colors: Option<Colors>,
+}
+
+#[derive(Deserialize)]
+struct Colors {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum DatabaseError {
+    Io(io::Error),
+    Parse(String),
+}
+
+impl Display for DatabaseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DatabaseError::Io(err) => write!(f, "{}", err),
+            DatabaseError::Parse(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for DatabaseError {}
+
+impl From<io::Error> for DatabaseError {
+    fn from(err: io::Error) -> Self {
+        DatabaseError::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::Color;
+
+    #[test]
+    fn lookup_by_sha1() {
+        let database = Database::parse(
+            r##"[
+                {
+                    "title": "Example",
+                    "authors": ["Someone"],
+                    "release": "2024",
+                    "roms": {
+                        "A9993E364706816ABA3E25717850C26C9CD0D89D": {
+                            "file": "example.ch8",
+                            "platforms": ["unknownPlatform", "superchip"],
+                            "quirkyPlatforms": { "superchip": { "shift": false, "wrap": true } },
+                            "tickrate": 30,
+                            "keys": { "up": 5 },
+                            "colors": { "pixels": ["#101010", "#ffcc00"] }
+                        }
+                    }
+                }
+            ]"##,
+        )
+        .unwrap();
+
+        assert_eq!(database.len(), 1);
+        assert!(database.lookup(b"abd").is_none());
+
+        let info = database.lookup(b"abc").unwrap();
+        assert_eq!(info.title, "Example");
+        assert_eq!(info.platform, Some(Platform::Schip));
+        assert_eq!(
+            info.quirks,
+            Some(Quirks {
+                shift_uses_vy: true,
+                clip_sprites: false,
+                ..Platform::Schip.quirks()
+            })
+        );
+        assert_eq!(info.tick_rate, Some(30));
+        assert_eq!(info.keys["up"], 5);
+        assert_eq!(info.palette.unwrap().foreground, Color([0xFF, 0xCC, 0x00]));
+    }
+
+    #[test]
+    fn configure() {
+        let info = RomInfo {
+            title: String::from("Example"),
+            authors: Vec::new(),
+            platform: Some(Platform::Vip),
+            quirks: Some(Quirks {
+                vf_reset: false,
+                ..Platform::Vip.quirks()
+            }),
+            tick_rate: Some(30),
+            keys: BTreeMap::new(),
+            palette: None,
+        };
+
+        let emulator = info.configure(Emulator::new().with_tick_rate(7).with_stack_depth(16));
+        assert_eq!(emulator.quirks(), info.quirks.unwrap());
+        assert_eq!(emulator.tick_rate(), 30);
+        assert_eq!(emulator.stack_capacity(), Platform::Vip.stack_depth());
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(Database::parse("[]").unwrap().is_empty());
+        assert!(Database::parse("{}").is_err());
+    }
+}
@@ -0,0 +1,270 @@
+use std::io::{self, BufRead, Write};
+
+use crate::{data::Addr, emulation::Emulator, opcode::Opcode};
+
+/// Largest number of instructions `continue` executes before giving up on reaching a
+/// breakpoint.
+const CONTINUE_LIMIT: u64 = 10_000_000;
+
+const HELP: &str = "\
+commands:
+  s, step [n]              execute n instructions (default 1)
+  sb, step-back [n]        undo n instructions (default 1)
+  c, continue              execute until a breakpoint is reached or the program stalls
+  rc, reverse-continue     undo instructions until a breakpoint is reached
+  b, break <addr>          set a breakpoint at a hexadecimal address
+  d, delete <addr>         remove a breakpoint
+  r, regs                  print registers
+  q, quit                  exit the debugger";
+
+/// [Debugger] is a line oriented interactive debugger which can step execution both
+/// forwards and backwards.
+pub struct Debugger {
+    emulator: Emulator,
+    breakpoints: Vec<Addr>,
+}
+
+impl Debugger {
+    /// Constructs a debugger around an emulator which has a program loaded. The emulator
+    /// should have rewind history enabled for stepping backwards to work.
+    pub fn new(emulator: Emulator) -> Self {
+        Debugger {
+            emulator,
+            breakpoints: Vec::new(),
+        }
+    }
+
+    /// Reads commands from `input` until it is exhausted or the user quits.
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, w: &mut W) -> io::Result<()> {
+        self.write_location(w)?;
+        write!(w, "> ")?;
+        w.flush()?;
+
+        for line in input.lines() {
+            let line = line?;
+            let mut words = line.split_whitespace();
+            let command = words.next().unwrap_or("");
+            let argument = words.next();
+
+            match command {
+                "" => {}
+                "s" | "step" => match parse_count(argument) {
+                    Some(count) => self.step(count, w)?,
+                    None => writeln!(w, "expected a count")?,
+                },
+
+                "sb" | "step-back" => match parse_count(argument) {
+                    Some(count) => self.step_back(count, w)?,
+                    None => writeln!(w, "expected a count")?,
+                },
+
+                "c" | "continue" => self.continue_forward(w)?,
+                "rc" | "reverse-continue" => {
+                    if !self.emulator.reverse_continue(&self.breakpoints) {
+                        writeln!(w, "reached the start of recorded history")?;
+                    }
+
+                    self.write_location(w)?;
+                }
+
+                "b" | "break" => match argument.and_then(parse_addr) {
+                    Some(addr) => {
+                        if !self.breakpoints.contains(&addr) {
+                            self.breakpoints.push(addr);
+                        }
+                    }
+                    None => writeln!(w, "expected an address")?,
+                },
+
+                "d" | "delete" => match argument.and_then(parse_addr) {
+                    Some(addr) => self.breakpoints.retain(|&b| b != addr),
+                    None => writeln!(w, "expected an address")?,
+                },
+
+                "r" | "regs" => self.write_registers(w)?,
+                "q" | "quit" => return Ok(()),
+                _ => writeln!(w, "{}", HELP)?,
+            }
+
+            write!(w, "> ")?;
+            w.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn step<W: Write>(&mut self, count: usize, w: &mut W) -> io::Result<()> {
+        for i in 0..count {
+            if let Err(err) = self.emulator.step() {
+                writeln!(w, "error: {}", err)?;
+                break;
+            }
+
+            if i + 1 < count && self.at_breakpoint() {
+                break;
+            }
+        }
+
+        self.write_location(w)
+    }
+
+    fn step_back<W: Write>(&mut self, count: usize, w: &mut W) -> io::Result<()> {
+        let undone = self.emulator.rewind(count);
+        if undone < count {
+            writeln!(w, "reached the start of recorded history")?;
+        }
+
+        self.write_location(w)
+    }
+
+    /// Executes until a breakpoint is reached, stopping early on an error, when an
+    /// instruction leaves the program counter where it was, as `JP` to itself or
+    /// `LD Vx, K` waiting for a key do, or after [CONTINUE_LIMIT] instructions.
+    fn continue_forward<W: Write>(&mut self, w: &mut W) -> io::Result<()> {
+        for _ in 0..CONTINUE_LIMIT {
+            let pc = self.emulator.program_counter();
+            match self.emulator.step() {
+                Err(err) => {
+                    writeln!(w, "error: {}", err)?;
+                    return self.write_location(w);
+                }
+
+                Ok(Opcode::LdK(_)) if self.emulator.program_counter() == pc => {
+                    writeln!(w, "stopped: waiting for a key press")?;
+                    return self.write_location(w);
+                }
+
+                Ok(_) if self.emulator.program_counter() == pc => {
+                    writeln!(w, "stopped: program counter is stuck")?;
+                    return self.write_location(w);
+                }
+
+                Ok(_) => {}
+            }
+
+            if self.at_breakpoint() {
+                return self.write_location(w);
+            }
+        }
+
+        writeln!(
+            w,
+            "stopped: no breakpoint reached after {} instructions",
+            CONTINUE_LIMIT
+        )?;
+
+        self.write_location(w)
+    }
+
+    fn at_breakpoint(&self) -> bool {
+        self.breakpoints.contains(&self.emulator.program_counter())
+    }
+
+    fn write_location<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let pc = self.emulator.program_counter() as usize;
+        let memory = self.emulator.memory();
+        let bytes = [
+            memory.get(pc).copied().unwrap_or(0),
+            memory.get(pc + 1).copied().unwrap_or(0),
+        ];
+
+        let opcode_text = match Opcode::decode(&bytes) {
+            Some(opcode) => format!("{}", opcode),
+            None => String::from("--"),
+        };
+
+        writeln!(
+            w,
+            "{:03X}   {:02X} {:02X}    {}",
+            pc, bytes[0], bytes[1], opcode_text
+        )
+    }
+
+    fn write_registers<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        for (offset, values) in self.emulator.registers().chunks(8).enumerate() {
+            let text: Vec<String> = values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| format!("V{:X}={:02X}", offset * 8 + i, value))
+                .collect();
+
+            writeln!(w, "{}", text.join(" "))?;
+        }
+
+        writeln!(
+            w,
+            "I={:03X} SP={} DT={:02X} ST={:02X} cycles={} history={}",
+            self.emulator.address_register(),
+            self.emulator.stack_depth(),
+            self.emulator.delay_timer(),
+            self.emulator.sound_timer(),
+            self.emulator.cycles(),
+            self.emulator.history_len(),
+        )
+    }
+}
+
+/// Parses the optional count of a stepping command, which defaults to 1. Returns None
+/// if the count is given but is not a number.
+fn parse_count(argument: Option<&str>) -> Option<usize> {
+    match argument {
+        Some(argument) => argument.parse().ok(),
+        None => Some(1),
+    }
+}
+
+fn parse_addr(argument: &str) -> Option<Addr> {
+    let digits = argument.trim_start_matches("0x");
+    Addr::from_str_radix(digits, 16).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 200: LD V0, 0x01
+    // 202: ADD V0, 0x01
+    // 204: SE V0, 0x04
+    // 206: JP 0x202
+    // 208: LD V1, K
+    const PROGRAM: [u8; 10] = [0x60, 0x01, 0x70, 0x01, 0x30, 0x04, 0x12, 0x02, 0xF1, 0x0A];
+
+    fn run(program: &[u8], commands: &str) -> String {
+        let mut emulator = Emulator::new().with_rewind(1000);
+        emulator.load(program).unwrap();
+
+        let mut out = Vec::new();
+        Debugger::new(emulator)
+            .run(commands.as_bytes(), &mut out)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn step() {
+        let out = run(&PROGRAM, "s 2\nsb\ns x\nr\n");
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[1], "> 204   30 04    SE   V0, 0x04");
+        assert_eq!(lines[2], "> 202   70 01    ADD  V0, 0x01");
+        assert_eq!(lines[3], "> expected a count");
+        assert!(lines[4].starts_with("> V0=01 V1=00"));
+    }
+
+    #[test]
+    fn continue_to_breakpoint() {
+        let out = run(&PROGRAM, "b 206\nc\nc\nr\n");
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[1], "> > 206   12 02    JP   0x202");
+        assert_eq!(lines[2], "> 206   12 02    JP   0x202");
+        assert!(lines[3].starts_with("> V0=03"));
+    }
+
+    #[test]
+    fn continue_stops_when_stuck() {
+        let out = run(&PROGRAM, "c\n");
+        assert!(out.contains("stopped: waiting for a key press\n208   F1 0A"));
+
+        let out = run(&[0x12, 0x00], "c\n");
+        assert!(out.contains("stopped: program counter is stuck\n200   12 00"));
+    }
+}
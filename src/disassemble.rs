@@ -1,8 +1,123 @@
-use std::io;
+use std::{
+    fmt::{self, Display, Formatter},
+    io,
+    str::FromStr,
+};
 
-use crate::opcode::Opcode;
+use serde_json::{json, Value};
 
-const DEFAULT_START_ADDR: u16 = 0x200;
+use crate::{
+    emulation::START_ADDRESS,
+    opcode::{Opcode, Operand},
+    symbols::Symbols,
+};
+
+/// [Format] selects how the [Disassembler] lays out its output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// Column aligned assembly listing.
+    #[default]
+    Text,
+
+    /// A JSON array with one object per instruction holding its address, raw bytes,
+    /// [Opcode] variant name, structured operands and text form:
+    ///
+    /// ```json
+    /// {"address":514,"bytes":[97,10],"opcode":"LdImm","operands":[{"kind":"register","value":1},{"kind":"byte","value":10}],"text":"LD   V1, 0x0A"}
+    /// ```
+    ///
+    /// Undecodable words and data have a null `opcode` and no operands. Labels and
+    /// comments from the symbols are included as `label` and `comment` when present.
+    Json,
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Format::Text => write!(f, "text"),
+            Format::Json => write!(f, "json"),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("text") {
+            Ok(Format::Text)
+        } else if s.eq_ignore_ascii_case("json") {
+            Ok(Format::Json)
+        } else {
+            Err(ParseFormatError)
+        }
+    }
+}
+
+/// [ParseFormatError] is returned when parsing an unknown [Format].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseFormatError;
+
+impl Display for ParseFormatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown format, expected text or json")
+    }
+}
+
+impl std::error::Error for ParseFormatError {}
+
+/// [Dialect] selects the assembly syntax the [Disassembler] writes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dialect {
+    /// The syntax of Cowgod's technical reference, as written by [Opcode]'s `Display`:
+    /// `ADD V1, 0x02`.
+    #[default]
+    Cowgod,
+
+    /// The syntax of the Octo assembler: `v1 += 0x02`, `i := label`, `sprite v0 v1 5`.
+    Octo,
+
+    /// The syntax of the CHIPPER assembler: `ADD V1, #02`.
+    Chipper,
+}
+
+impl Display for Dialect {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Dialect::Cowgod => write!(f, "cowgod"),
+            Dialect::Octo => write!(f, "octo"),
+            Dialect::Chipper => write!(f, "chipper"),
+        }
+    }
+}
+
+impl FromStr for Dialect {
+    type Err = ParseDialectError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("cowgod") {
+            Ok(Dialect::Cowgod)
+        } else if s.eq_ignore_ascii_case("octo") {
+            Ok(Dialect::Octo)
+        } else if s.eq_ignore_ascii_case("chipper") {
+            Ok(Dialect::Chipper)
+        } else {
+            Err(ParseDialectError)
+        }
+    }
+}
+
+/// [ParseDialectError] is returned when parsing an unknown [Dialect].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseDialectError;
+
+impl Display for ParseDialectError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown dialect, expected cowgod, octo or chipper")
+    }
+}
+
+impl std::error::Error for ParseDialectError {}
 
 /// [Disassembler] provides facilities for disassembling Chip8 machine code into assembly
 /// instructions.
@@ -10,6 +125,9 @@ pub struct Disassembler {
     include_addresses: bool,
     start_address: u16,
     include_binary: bool,
+    symbols: Symbols,
+    format: Format,
+    dialect: Dialect,
 }
 
 impl Disassembler {
@@ -17,8 +135,11 @@ impl Disassembler {
     pub fn new() -> Self {
         Disassembler {
             include_addresses: false,
-            start_address: DEFAULT_START_ADDR,
+            start_address: START_ADDRESS,
             include_binary: false,
+            symbols: Symbols::new(),
+            format: Format::Text,
+            dialect: Dialect::Cowgod,
         }
     }
 
@@ -26,87 +147,307 @@ impl Disassembler {
     pub fn with_addresses(self, include_addresses: bool) -> Self {
         Disassembler {
             include_addresses,
-            start_address: self.start_address,
-            include_binary: self.include_binary,
+            ..self
         }
     }
 
     /// Sets the address to start at when printing addresses.
     pub fn with_start_address(self, start_address: u16) -> Self {
         Disassembler {
-            include_addresses: self.include_addresses,
             start_address,
-            include_binary: self.include_binary,
+            ..self
         }
     }
 
     /// Enables/disables printing binary along with instructions.
     pub fn with_binary(self, include_binary: bool) -> Self {
         Disassembler {
-            include_addresses: self.include_addresses,
-            start_address: self.start_address,
             include_binary,
+            ..self
         }
     }
 
+    /// Sets the symbols used to label addresses, comment instructions and print data
+    /// regions as data directives.
+    pub fn with_symbols(self, symbols: Symbols) -> Self {
+        Disassembler { symbols, ..self }
+    }
+
+    /// Sets the output format. Addresses and binary are always included in JSON.
+    pub fn with_format(self, format: Format) -> Self {
+        Disassembler { format, ..self }
+    }
+
+    /// Sets the assembly syntax used for instructions, labels, comments and data.
+    pub fn with_dialect(self, dialect: Dialect) -> Self {
+        Disassembler { dialect, ..self }
+    }
+
     /// Disassembles a given program writing assembly instructions to a given writer.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// This method panics if the length of `program` is not even.
+    /// Returns an [io::ErrorKind::InvalidInput] error without writing anything if the
+    /// length of `program` is not even.
     pub fn disassemble<W: io::Write>(&self, program: &[u8], w: &mut W) -> io::Result<()> {
-        if program.len() % 2 != 0 {
-            panic!("program length must be equal");
+        if !program.len().is_multiple_of(2) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("program length {} is not even", program.len()),
+            ));
+        }
+
+        let mut objects = Vec::new();
+        let mut index = 0;
+        while index < program.len() {
+            // Data regions of odd length leave the instructions after them unaligned, so
+            // a single byte may remain at the end.
+            let addr = index as u16 + self.start_address;
+            let remaining = program.len() - index;
+            let data_end = self.symbols.data_end(addr);
+            let len = match data_end {
+                Some(end) => ((end - addr) as usize + 1).min(2).min(remaining),
+                None => remaining.min(2),
+            };
+
+            let bytes = &program[index..index + len];
+            let opcode = match (data_end, len) {
+                (None, 2) => Opcode::decode(bytes),
+                _ => None,
+            };
+
+            let text = match (opcode, data_end) {
+                (Some(opcode), _) => self.instruction_text(opcode, bytes),
+                (None, Some(_)) => self.data_text(bytes),
+                (None, None) => self.unknown_text(bytes),
+            };
+
+            match self.format {
+                Format::Text => self.write_instruction(&text, index, bytes, w)?,
+                Format::Json => objects.push(self.json_instruction(opcode, &text, addr, bytes)),
+            }
+
+            index += len;
         }
 
-        for i in (0..program.len() - 1).step_by(2) {
-            let opcode_bytes = &program[i..i + 2];
-            let opcode = Opcode::decode(opcode_bytes);
-            self.write_instruction(&opcode, i, opcode_bytes, w)?;
+        if self.format == Format::Json {
+            writeln!(w, "[")?;
+            for (i, object) in objects.iter().enumerate() {
+                let separator = if i + 1 < objects.len() { "," } else { "" };
+                writeln!(w, "  {}{}", object, separator)?;
+            }
+
+            writeln!(w, "]")?;
         }
 
         Ok(())
     }
 
+    fn instruction_text(&self, opcode: Opcode, bytes: &[u8]) -> String {
+        match self.dialect {
+            Dialect::Cowgod | Dialect::Chipper => self.mnemonic_text(opcode),
+            Dialect::Octo => self.octo_text(opcode, bytes),
+        }
+    }
+
+    /// Writes an instruction as a mnemonic followed by its operands.
+    fn mnemonic_text(&self, opcode: Opcode) -> String {
+        let mut operands: Vec<String> = opcode
+            .operands()
+            .iter()
+            .map(|&operand| self.operand_text(operand))
+            .collect();
+
+        // CHIPPER lists the Vy register of shifts even though it is ignored by default.
+        if let (Dialect::Chipper, Opcode::Shr(_, r) | Opcode::Shl(_, r)) = (self.dialect, opcode) {
+            operands.push(self.operand_text(Operand::Register(r)));
+        }
+
+        if operands.is_empty() {
+            return String::from(opcode.mnemonic());
+        }
+
+        format!("{:<4} {}", opcode.mnemonic(), operands.join(", "))
+    }
+
+    fn octo_text(&self, opcode: Opcode, bytes: &[u8]) -> String {
+        use Opcode::*;
+
+        let v = |r| self.operand_text(Operand::Register(r));
+        let byte = |x| self.operand_text(Operand::Byte(x));
+        let addr = |a| self.operand_text(Operand::Addr(a));
+        match opcode {
+            Sys(_) => self.unknown_text(bytes),
+            Cls => String::from("clear"),
+            Ret => String::from("return"),
+            Jp(a) => format!("jump {}", addr(a)),
+            // Octo calls a subroutine by naming it and needs `:call` for bare addresses.
+            Call(a) => match self.symbols.label(a) {
+                Some(name) => String::from(name),
+                None => format!(":call {}", addr(a)),
+            },
+            Se(r, x) => format!("if {} != {} then", v(r), byte(x)),
+            Sne(r, x) => format!("if {} == {} then", v(r), byte(x)),
+            Sev(r1, r2) => format!("if {} != {} then", v(r1), v(r2)),
+            Snev(r1, r2) => format!("if {} == {} then", v(r1), v(r2)),
+            LdImm(r, x) => format!("{} := {}", v(r), byte(x)),
+            AddImm(r, x) => format!("{} += {}", v(r), byte(x)),
+            Ld(r1, r2) => format!("{} := {}", v(r1), v(r2)),
+            Or(r1, r2) => format!("{} |= {}", v(r1), v(r2)),
+            And(r1, r2) => format!("{} &= {}", v(r1), v(r2)),
+            Xor(r1, r2) => format!("{} ^= {}", v(r1), v(r2)),
+            Add(r1, r2) => format!("{} += {}", v(r1), v(r2)),
+            Sub(r1, r2) => format!("{} -= {}", v(r1), v(r2)),
+            Shr(r1, r2) => format!("{} >>= {}", v(r1), v(r2)),
+            Subn(r1, r2) => format!("{} =- {}", v(r1), v(r2)),
+            Shl(r1, r2) => format!("{} <<= {}", v(r1), v(r2)),
+            Ldi(a) => format!("i := {}", addr(a)),
+            JpV0(a) => format!("jump0 {}", addr(a)),
+            Rnd(r, x) => format!("{} := random {}", v(r), byte(x)),
+            Drw(r1, r2, n) => format!("sprite {} {} {}", v(r1), v(r2), n.as_u8()),
+            Skp(r) => format!("if {} -key then", v(r)),
+            Sknp(r) => format!("if {} key then", v(r)),
+            LdVDt(r) => format!("{} := delay", v(r)),
+            LdK(r) => format!("{} := key", v(r)),
+            LdDtV(r) => format!("delay := {}", v(r)),
+            LdStV(r) => format!("buzzer := {}", v(r)),
+            AddI(r) => format!("i += {}", v(r)),
+            LdF(r) => format!("i := hex {}", v(r)),
+            LdB(r) => format!("bcd {}", v(r)),
+            Dump(r) => format!("save {}", v(r)),
+            Restore(r) => format!("load {}", v(r)),
+        }
+    }
+
+    fn operand_text(&self, operand: Operand) -> String {
+        if let Operand::Addr(addr) = operand {
+            if let Some(name) = self.symbols.label(addr) {
+                return String::from(name);
+            }
+        }
+
+        match (self.dialect, operand) {
+            (Dialect::Cowgod, _) => operand.to_string(),
+            (Dialect::Octo, Operand::Register(r)) => format!("v{:x}", r.0.as_u8()),
+            (Dialect::Chipper, Operand::Register(r)) => format!("V{:X}", r.0.as_u8()),
+            (Dialect::Octo, Operand::Nibble(n)) | (Dialect::Chipper, Operand::Nibble(n)) => {
+                n.as_u8().to_string()
+            }
+            (Dialect::Chipper, Operand::Byte(x)) => format!("#{:02X}", x),
+            (Dialect::Chipper, Operand::Addr(addr)) => format!("#{:03X}", addr),
+            _ => operand.to_string(),
+        }
+    }
+
+    fn data_text(&self, bytes: &[u8]) -> String {
+        let bytes: Vec<String> = bytes.iter().map(|b| self.byte_text(*b)).collect();
+        match self.dialect {
+            Dialect::Cowgod | Dialect::Chipper => format!("DB   {}", bytes.join(", ")),
+            Dialect::Octo => bytes.join(" "),
+        }
+    }
+
+    /// Writes a word which is not an instruction, as data where the dialect allows it.
+    fn unknown_text(&self, bytes: &[u8]) -> String {
+        match (self.dialect, bytes) {
+            (Dialect::Cowgod, _) => String::from("--"),
+            (Dialect::Chipper, [high, low]) => format!("DW   #{:02X}{:02X}", high, low),
+            _ => self.data_text(bytes),
+        }
+    }
+
+    fn byte_text(&self, byte: u8) -> String {
+        match self.dialect {
+            Dialect::Cowgod | Dialect::Octo => format!("0x{:02X}", byte),
+            Dialect::Chipper => format!("#{:02X}", byte),
+        }
+    }
+
+    fn json_instruction(
+        &self,
+        opcode: Option<Opcode>,
+        text: &str,
+        addr: u16,
+        bytes: &[u8],
+    ) -> Value {
+        let operands: Vec<Value> = opcode
+            .iter()
+            .flat_map(|opcode| opcode.operands().to_vec())
+            .map(|operand| match operand {
+                Operand::Register(r) => json!({ "kind": "register", "value": r.0.as_u8() }),
+                Operand::Byte(x) => json!({ "kind": "byte", "value": x }),
+                Operand::Nibble(n) => json!({ "kind": "nibble", "value": n.as_u8() }),
+                Operand::Addr(addr) => match self.symbols.label(addr) {
+                    Some(name) => json!({ "kind": "address", "value": addr, "label": name }),
+                    None => json!({ "kind": "address", "value": addr }),
+                },
+                Operand::I => json!({ "kind": "i" }),
+                Operand::IndirectI => json!({ "kind": "indirect_i" }),
+                Operand::DelayTimer => json!({ "kind": "delay_timer" }),
+                Operand::SoundTimer => json!({ "kind": "sound_timer" }),
+                Operand::Key => json!({ "kind": "key" }),
+                Operand::Font => json!({ "kind": "font" }),
+                Operand::Bcd => json!({ "kind": "bcd" }),
+            })
+            .collect();
+
+        let mut object = json!({
+            "address": addr,
+            "bytes": bytes,
+            "opcode": opcode.map(|opcode| opcode.name()),
+            "operands": operands,
+            "text": text,
+        });
+
+        if let Some(label) = self.symbols.label(addr) {
+            object["label"] = json!(label);
+        }
+
+        if let Some(comment) = self.symbols.comment(addr) {
+            object["comment"] = json!(comment);
+        }
+
+        object
+    }
+
     fn write_instruction<W: io::Write>(
         &self,
-        opcode: &Option<Opcode>,
+        text: &str,
         index: usize,
         bytes: &[u8],
         w: &mut W,
     ) -> io::Result<()> {
-        debug_assert!(bytes.len() == 2);
-
-        let opcode_text = match opcode {
-            Some(opcode) => format!("{}", opcode),
-            None => String::from("--"),
-        };
+        debug_assert!(!bytes.is_empty() && bytes.len() <= 2);
 
         let addr = index as u16 + self.start_address;
-
-        match (self.include_addresses, self.include_binary) {
-            (true, true) => {
-                writeln!(
-                    w,
-                    "{:03X}   {:02X} {:02X}    {}",
-                    addr, bytes[0], bytes[1], opcode_text
-                )?;
+        if let Some(name) = self.symbols.label(addr) {
+            match self.dialect {
+                Dialect::Cowgod | Dialect::Chipper => writeln!(w, "{}:", name)?,
+                Dialect::Octo => writeln!(w, ": {}", name)?,
             }
+        }
 
-            (true, false) => {
-                writeln!(w, "{:03X}    {}", addr, opcode_text)?;
-            }
+        let binary = match bytes {
+            [high, low] => format!("{:02X} {:02X}", high, low),
+            [byte] => format!("{:02X}   ", byte),
+            _ => unreachable!(),
+        };
 
-            (false, true) => {
-                writeln!(w, "{:02X} {:02X}    {}", bytes[0], bytes[1], opcode_text)?;
-            }
+        match (self.include_addresses, self.include_binary) {
+            (true, true) => write!(w, "{:03X}   {}    {}", addr, binary, text)?,
+            (true, false) => write!(w, "{:03X}    {}", addr, text)?,
+            (false, true) => write!(w, "{}    {}", binary, text)?,
+            (false, false) => write!(w, "{}", text)?,
+        }
 
-            (false, false) => {
-                writeln!(w, "{}", opcode_text)?;
+        if let Some(comment) = self.symbols.comment(addr) {
+            match self.dialect {
+                Dialect::Cowgod | Dialect::Chipper => write!(w, "  ; {}", comment)?,
+                Dialect::Octo => write!(w, "  # {}", comment)?,
             }
         }
 
-        Ok(())
+        writeln!(w)
     }
 }
 
@@ -116,3 +457,158 @@ impl Default for Disassembler {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // CLS; LD V1, 0x0A; SHL V1; (invalid); DRW V1, V2, 5
+    const PROGRAM: [u8; 10] = [0x00, 0xE0, 0x61, 0x0A, 0x81, 0x2E, 0x51, 0x21, 0xD1, 0x25];
+
+    fn disassemble(disassembler: Disassembler) -> String {
+        let mut out = Vec::new();
+        disassembler.disassemble(&PROGRAM, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn plain_listing() {
+        let expected = "\
+CLS
+LD   V1, 0x0A
+SHL  V1
+--
+DRW  V1, V2, 0x5
+";
+        assert_eq!(disassemble(Disassembler::new()), expected);
+    }
+
+    #[test]
+    fn listing_with_addresses_and_binary() {
+        let disassembler = Disassembler::new()
+            .with_addresses(true)
+            .with_binary(true)
+            .with_start_address(0x300);
+
+        let expected = "\
+300   00 E0    CLS
+302   61 0A    LD   V1, 0x0A
+304   81 2E    SHL  V1
+306   51 21    --
+308   D1 25    DRW  V1, V2, 0x5
+";
+        assert_eq!(disassemble(disassembler), expected);
+    }
+
+    #[test]
+    fn rejects_odd_length() {
+        let mut out = Vec::new();
+        let err = Disassembler::new().disassemble(&PROGRAM[..3], &mut out);
+        assert_eq!(err.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert!(out.is_empty());
+
+        Disassembler::new().disassemble(&[], &mut out).unwrap();
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn listing_with_symbols() {
+        let symbols = Symbols::parse(
+            "label 200 main\nlabel 20A loop\ncomment 202 counter\ndata 206 206 flags\n",
+        )
+        .unwrap();
+
+        // CLS; LD V1, 0x0A; JP 0x20A; 0x51 (data); CALL 0x20A
+        let program = [0x00, 0xE0, 0x61, 0x0A, 0x12, 0x0A, 0x51, 0x22, 0x0A, 0x00];
+        let mut out = Vec::new();
+        Disassembler::new()
+            .with_addresses(true)
+            .with_binary(true)
+            .with_symbols(symbols)
+            .disassemble(&program, &mut out)
+            .unwrap();
+
+        let expected = "\
+main:
+200   00 E0    CLS
+202   61 0A    LD   V1, 0x0A  ; counter
+204   12 0A    JP   loop
+flags:
+206   51       DB   0x51
+207   22 0A    CALL loop
+209   00       --
+";
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    #[test]
+    fn dialects() {
+        let symbols = Symbols::parse("label 200 main\ncomment 202 counter").unwrap();
+        let octo = Disassembler::new()
+            .with_dialect(Dialect::Octo)
+            .with_symbols(symbols);
+
+        let expected = "\
+: main
+clear
+v1 := 0x0A  # counter
+v1 <<= v2
+0x51 0x21
+sprite v1 v2 5
+";
+        assert_eq!(disassemble(octo), expected);
+
+        let chipper = Disassembler::new().with_dialect(Dialect::Chipper);
+        let expected = "\
+CLS
+LD   V1, #0A
+SHL  V1, V2
+DW   #5121
+DRW  V1, V2, 5
+";
+        assert_eq!(disassemble(chipper), expected);
+        assert_eq!("Octo".parse(), Ok(Dialect::Octo));
+    }
+
+    #[test]
+    fn json_listing() {
+        let symbols = Symbols::parse("label 200 main\ncomment 202 counter").unwrap();
+        let mut out = Vec::new();
+        Disassembler::new()
+            .with_format(Format::Json)
+            .with_symbols(symbols)
+            .disassemble(&PROGRAM[..8], &mut out)
+            .unwrap();
+
+        let json: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 4);
+        assert_eq!(
+            json[0],
+            json!({
+                "address": 0x200,
+                "bytes": [0x00, 0xE0],
+                "opcode": "Cls",
+                "operands": [],
+                "text": "CLS",
+                "label": "main",
+            })
+        );
+        assert_eq!(
+            json[1]["operands"],
+            json!([{ "kind": "register", "value": 1 }, { "kind": "byte", "value": 0x0A }])
+        );
+        assert_eq!(json[1]["comment"], "counter");
+        assert_eq!(json[3]["opcode"], Value::Null);
+        assert_eq!(json[3]["text"], "--");
+        assert_eq!("JSON".parse(), Ok(Format::Json));
+    }
+
+    #[test]
+    fn listing_with_addresses_or_binary() {
+        let addresses = Disassembler::new().with_addresses(true);
+        assert!(disassemble(addresses).starts_with("200    CLS\n202    LD   V1, 0x0A\n"));
+
+        let binary = Disassembler::new().with_binary(true);
+        assert!(disassemble(binary).starts_with("00 E0    CLS\n61 0A    LD   V1, 0x0A\n"));
+    }
+}
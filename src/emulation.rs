@@ -1,44 +1,179 @@
-use crate::data::Register;
+#[cfg(feature = "std")]
+mod rewind;
+#[cfg(feature = "std")]
+mod savestate;
+#[cfg(test)]
+mod test;
+
+use core::fmt;
+
+#[cfg(feature = "std")]
+use self::rewind::RewindBuffer;
+#[cfg(feature = "std")]
+pub use self::savestate::SaveStateError;
+use crate::{
+    data::{Addr, Register},
+    framebuffer::Framebuffer,
+    opcode::Opcode,
+    quirks::{Platform, Quirks},
+};
 
 /// Size of emulator RAM in number of bytes.
 const MEMORY_SIZE: usize = 4096;
 
-/// Size of the stack in number of addresses (u16).
-const STACK_SIZE: usize = 16;
+/// Maximum depth of the stack in number of addresses (u16).
+pub const MAX_STACK_DEPTH: usize = 16;
+
+/// Address at which programs are loaded and start executing.
+pub const START_ADDRESS: Addr = 0x200;
+
+/// Address at which the built-in hexadecimal font is loaded.
+pub const FONT_ADDRESS: Addr = 0x050;
 
-#[derive(Debug)]
-pub enum EmulationError {
+/// Default number of instructions executed per 60 Hz frame.
+pub const DEFAULT_TICK_RATE: u32 = 10;
+
+/// Sprites for the hexadecimal digits 0 through F, 5 bytes each.
+const FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+/// Returns the stack depth of the platform whose preset `quirks` are, or
+/// [MAX_STACK_DEPTH] for quirks which do not match any preset.
+pub fn default_stack_depth(quirks: Quirks) -> usize {
+    Platform::from_quirks(quirks).map_or(MAX_STACK_DEPTH, |platform| platform.stack_depth())
+}
+
+/// [Fault] describes why the emulator could not continue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// `CALL` was executed with every stack slot in use.
     StackOverflow,
+
+    /// `RET` was executed with an empty stack.
     StackUnderflow,
-    OutOfMemory,
-    InvalidInstruction(u16),
+
+    /// An access of `len` bytes at `address` extends past the end of memory.
+    OutOfMemory { address: usize, len: usize },
+
+    /// The instruction word does not decode to any [Opcode].
+    InvalidInstruction,
+
+    /// The program counter is odd or points past the end of memory.
+    InvalidProgramCounter,
+
+    /// A program of `len` bytes does not fit into memory at the start address.
+    ProgramTooLarge { len: usize },
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::StackOverflow => write!(f, "stack overflow"),
+            Fault::StackUnderflow => write!(f, "stack underflow"),
+            Fault::OutOfMemory { address, len } => write!(
+                f,
+                "memory access of {} bytes at 0x{:03X} is out of bounds",
+                len, address
+            ),
+            Fault::InvalidInstruction => write!(f, "invalid instruction"),
+            Fault::InvalidProgramCounter => write!(f, "invalid program counter"),
+            Fault::ProgramTooLarge { len } => {
+                write!(f, "program of {} bytes does not fit into memory", len)
+            }
+        }
+    }
+}
+
+/// [EmulationError] is returned when the emulator stops because of a [Fault], along
+/// with where in the program it happened.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EmulationError {
+    pub fault: Fault,
+
+    /// Address of the instruction which caused the fault.
+    pub pc: Addr,
+
+    /// The instruction word, if one could be fetched.
+    pub opcode: Option<u16>,
+
+    /// Number of instructions executed before the fault.
+    pub cycle: u64,
+}
+
+impl fmt::Display for EmulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Fault::ProgramTooLarge { .. } = self.fault {
+            return write!(f, "{}", self.fault);
+        }
+
+        write!(f, "{} at PC 0x{:03X}", self.fault, self.pc)?;
+        match self.opcode {
+            Some(opcode) => write!(f, " (opcode {:04X}, cycle {})", opcode, self.cycle),
+            None => write!(f, " (cycle {})", self.cycle),
+        }
+    }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for EmulationError {}
+
 /// [Memory] is a 4KiB array of bytes used as RAM for the Chip-8 emulator.
+#[derive(Clone)]
 struct Memory([u8; MEMORY_SIZE]);
 
 impl Memory {
     /// Loads a chunk of data into memory at a given offset. Returns an out-of-memory
     /// error if the given data chunk is too large.
-    fn load(&mut self, offset: usize, data: &[u8]) -> Result<(), EmulationError> {
-        if data.len() > MEMORY_SIZE - offset {
-            return Err(EmulationError::OutOfMemory);
-        }
-
-        for i in 0..data.len() {
-            self.0[i + offset] = data[i];
+    fn load(&mut self, offset: usize, data: &[u8]) -> Result<(), Fault> {
+        if offset > MEMORY_SIZE || data.len() > MEMORY_SIZE - offset {
+            return Err(Fault::OutOfMemory {
+                address: offset,
+                len: data.len(),
+            });
         }
 
+        self.0[offset..offset + data.len()].copy_from_slice(data);
         Ok(())
     }
 
-    /// Fetches a 2-byte instruction at a given address.
-    fn fetch_instruction(&self, address: u16) -> &[u8] {
-        assert_eq!(address % 2, 0, "instructions must be 16-bit aligned");
-        assert!((address as usize) < MEMORY_SIZE - 2);
+    /// Reads a chunk of memory starting at a given offset. Returns an out-of-memory
+    /// error if the chunk extends past the end of memory.
+    fn read(&self, offset: usize, len: usize) -> Result<&[u8], Fault> {
+        if offset > MEMORY_SIZE || len > MEMORY_SIZE - offset {
+            return Err(Fault::OutOfMemory {
+                address: offset,
+                len,
+            });
+        }
 
+        Ok(&self.0[offset..offset + len])
+    }
+
+    /// Fetches the 16-bit instruction word at a given address. Returns an error if the
+    /// address is not aligned or the instruction extends past the end of memory.
+    fn fetch_instruction(&self, address: u16) -> Result<u16, Fault> {
         let index = address as usize;
-        &self.0[index..index + 2]
+        if !index.is_multiple_of(2) || index + 2 > MEMORY_SIZE {
+            return Err(Fault::InvalidProgramCounter);
+        }
+
+        Ok(u16::from_be_bytes([self.0[index], self.0[index + 1]]))
     }
 }
 
@@ -48,43 +183,121 @@ impl Default for Memory {
     }
 }
 
+/// [OverflowMode] selects what happens when `CALL` is executed with a full stack.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowMode {
+    /// Stops execution with a [Fault::StackOverflow].
+    #[default]
+    Error,
+
+    /// Wraps around and overwrites the oldest return address.
+    Wrap,
+}
+
+impl fmt::Display for OverflowMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OverflowMode::Error => write!(f, "error"),
+            OverflowMode::Wrap => write!(f, "wrap"),
+        }
+    }
+}
+
+impl core::str::FromStr for OverflowMode {
+    type Err = ParseOverflowModeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("error") {
+            Ok(OverflowMode::Error)
+        } else if s.eq_ignore_ascii_case("wrap") {
+            Ok(OverflowMode::Wrap)
+        } else {
+            Err(ParseOverflowModeError)
+        }
+    }
+}
+
+/// [ParseOverflowModeError] is returned when parsing an unknown [OverflowMode].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseOverflowModeError;
+
+impl fmt::Display for ParseOverflowModeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown overflow mode, expected error or wrap")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseOverflowModeError {}
+
 /// [Stack] is the program stack for the Chip-8 emulator. The stack is used to store
-/// return addresses for subroutine calls. 16 such addresses can be stored on the stack.
-/// Chip-8 only allows for up to 16 levels of nested subroutine calls.
-#[derive(Default)]
+/// return addresses for subroutine calls. It holds up to `depth` addresses, which is
+/// 12 on the COSMAC VIP and 16 on later interpreters, in a ring so that it can wrap
+/// around on overflow.
+#[derive(Clone)]
 struct Stack {
-    stack_index: usize,
-    memory: [u16; STACK_SIZE],
+    memory: [u16; MAX_STACK_DEPTH],
+    top: usize,
+    len: usize,
+    depth: usize,
+    overflow: OverflowMode,
 }
 
 impl Stack {
-    /// Pushes an address onto the stack. Returns a stack overflow error if the stack is
-    /// full and no more addresses can be pushed.
-    fn push(&mut self, addr: u16) -> Result<(), EmulationError> {
-        if self.stack_index >= STACK_SIZE - 1 {
-            return Err(EmulationError::StackOverflow);
+    /// Constructs an empty stack holding up to `depth` addresses.
+    fn new(depth: usize, overflow: OverflowMode) -> Self {
+        Stack {
+            memory: [0; MAX_STACK_DEPTH],
+            top: 0,
+            len: 0,
+            depth: depth.clamp(1, MAX_STACK_DEPTH),
+            overflow,
         }
+    }
 
-        self.stack_index += 1;
-        self.memory[self.stack_index] = addr;
+    /// Pushes an address onto the stack. If the stack is full this either returns a
+    /// stack overflow error or overwrites the oldest address, depending on the
+    /// overflow mode.
+    fn push(&mut self, addr: u16) -> Result<(), Fault> {
+        if self.len == self.depth {
+            if self.overflow == OverflowMode::Error {
+                return Err(Fault::StackOverflow);
+            }
+        } else {
+            self.len += 1;
+        }
+
+        self.memory[self.top] = addr;
+        self.top = (self.top + 1) % self.depth;
         Ok(())
     }
 
     /// Pops an address off of the top of the stack. Returns a stack underflow error if
     /// the stack is empty and not addresses can be popped off.
-    fn pop(&mut self) -> Result<u16, EmulationError> {
-        if self.stack_index == 0 {
-            return Err(EmulationError::StackUnderflow);
+    fn pop(&mut self) -> Result<u16, Fault> {
+        if self.len == 0 {
+            return Err(Fault::StackUnderflow);
         }
 
-        let addr = self.memory[self.stack_index];
-        self.stack_index -= 1;
-        Ok(addr)
+        self.len -= 1;
+        self.top = (self.top + self.depth - 1) % self.depth;
+        Ok(self.memory[self.top])
+    }
+
+    /// Returns the number of addresses currently on the stack.
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl Default for Stack {
+    fn default() -> Self {
+        Stack::new(MAX_STACK_DEPTH, OverflowMode::Error)
     }
 }
 
 /// [Registers] is a collection of 16 general purpose registers.
-#[derive(Default)]
+#[derive(Clone, Default)]
 struct Registers([u8; 16]);
 
 impl Registers {
@@ -101,7 +314,76 @@ impl Registers {
     }
 }
 
-#[derive(Default)]
+/// [Keypad] is the state of the 16-key hexadecimal keypad, one bit per key.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Keypad(u16);
+
+impl Keypad {
+    /// Constructs a keypad state from a bit mask with bit `n` set if key `n` is held.
+    pub fn from_bits(bits: u16) -> Self {
+        Keypad(bits)
+    }
+
+    /// Returns the keypad state as a bit mask with bit `n` set if key `n` is held.
+    pub fn bits(&self) -> u16 {
+        self.0
+    }
+
+    /// Presses or releases a given key.
+    pub fn set(&mut self, key: u8, pressed: bool) {
+        if pressed {
+            self.0 |= 1 << (key & 0x0F);
+        } else {
+            self.0 &= !(1 << (key & 0x0F));
+        }
+    }
+
+    /// Returns whether a given key is held down.
+    #[inline]
+    pub fn is_pressed(&self, key: u8) -> bool {
+        self.0 & (1 << (key & 0x0F)) != 0
+    }
+
+    /// Returns the lowest numbered key which is held down, if any.
+    pub fn first_pressed(&self) -> Option<u8> {
+        if self.0 == 0 {
+            None
+        } else {
+            Some(self.0.trailing_zeros() as u8)
+        }
+    }
+}
+
+/// [Rng] is a small xorshift generator backing the `RND` instruction. It is seeded
+/// explicitly so that runs of the emulator are reproducible.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Rng(u32);
+
+impl Rng {
+    /// Constructs a generator from a seed. A seed of zero is replaced with a fixed
+    /// non-zero value as xorshift would otherwise only ever produce zeros.
+    fn new(seed: u32) -> Self {
+        Rng(if seed == 0 { 0x9E37_79B9 } else { seed })
+    }
+
+    /// Produces the next random byte.
+    fn next_u8(&mut self) -> u8 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        (x >> 24) as u8
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Rng::new(0)
+    }
+}
+
+#[derive(Clone, Default)]
 struct EmulatorState {
     registers: Registers,
     address_register: u16, // aka. I
@@ -110,36 +392,451 @@ struct EmulatorState {
     sound_register: u8,
     stack: Stack,
     memory: Memory,
+    framebuffer: Framebuffer,
+    keypad: Keypad,
+    rng: Rng,
+    cycles: u64,
+    frames: u64,
+    frame_cycles: u32,
 }
 
 pub struct Emulator {
     start_address: u16,
+    tick_rate: u32,
+    seed: u32,
+    quirks: Quirks,
+
+    /// Stack depth set with [Emulator::with_stack_depth], if any; otherwise the depth
+    /// follows the quirks with [default_stack_depth].
+    stack_depth: Option<usize>,
+    stack_overflow: OverflowMode,
     state: EmulatorState,
+    #[cfg(feature = "std")]
+    rewind: Option<RewindBuffer>,
+    buzzer: bool,
 }
 
 impl Emulator {
     /// Constructs a new emulator with default options.
     pub fn new() -> Self {
         Emulator {
-            start_address: 0x200,
+            start_address: START_ADDRESS,
+            tick_rate: DEFAULT_TICK_RATE,
+            seed: 0,
+            quirks: Quirks::default(),
+            stack_depth: None,
+            stack_overflow: OverflowMode::Error,
             state: EmulatorState::default(),
+            #[cfg(feature = "std")]
+            rewind: None,
+            buzzer: false,
+        }
+    }
+
+    /// Sets the quirks used when executing instructions.
+    pub fn with_quirks(self, quirks: Quirks) -> Self {
+        Emulator { quirks, ..self }
+    }
+
+    /// Sets the number of instructions executed per 60 Hz frame.
+    pub fn with_tick_rate(self, tick_rate: u32) -> Self {
+        Emulator {
+            tick_rate: tick_rate.max(1),
+            ..self
+        }
+    }
+
+    /// Sets the seed for the random number generator used by `RND`.
+    pub fn with_seed(self, seed: u32) -> Self {
+        Emulator { seed, ..self }
+    }
+
+    /// Sets the number of nested subroutine calls the stack can hold, between 1 and
+    /// [MAX_STACK_DEPTH]. See [Platform::stack_depth] for the depth of each platform.
+    /// Without it the depth is that of the platform whose quirks are in use.
+    pub fn with_stack_depth(self, stack_depth: usize) -> Self {
+        Emulator {
+            stack_depth: Some(stack_depth.clamp(1, MAX_STACK_DEPTH)),
+            ..self
         }
     }
 
-    /// Executes a program written in Chip-8 machine code.
-    pub fn run(&mut self, program: &[u8]) -> Result<(), EmulationError> {
+    /// Sets whether `CALL` faults or wraps around when the stack is full.
+    pub fn with_stack_overflow(self, stack_overflow: OverflowMode) -> Self {
+        Emulator {
+            stack_overflow,
+            ..self
+        }
+    }
+
+    /// Resets the emulator and loads a program into memory ready to be executed.
+    pub fn load(&mut self, program: &[u8]) -> Result<(), EmulationError> {
         self.state = Default::default();
-        self.state
-            .memory
-            .load(self.start_address as usize, program)?;
+        let memory = &mut self.state.memory;
+        let loaded = memory
+            .load(FONT_ADDRESS as usize, &FONT)
+            .and_then(|_| memory.load(self.start_address as usize, program));
+
+        if loaded.is_err() {
+            return Err(EmulationError {
+                fault: Fault::ProgramTooLarge { len: program.len() },
+                pc: self.start_address,
+                opcode: None,
+                cycle: 0,
+            });
+        }
 
         self.state.program_counter = self.start_address;
-        self.emulation_loop()?;
+        self.state.stack = Stack::new(self.stack_capacity(), self.stack_overflow);
+        self.state.rng = Rng::new(self.seed);
+        self.buzzer = false;
+        #[cfg(feature = "std")]
+        if let Some(rewind) = &mut self.rewind {
+            rewind.clear();
+        }
+
         Ok(())
     }
 
-    fn emulation_loop(&mut self) -> Result<(), EmulationError> {
-        loop {}
+    /// Executes instructions until the end of the current 60 Hz frame.
+    pub fn run_frame(&mut self) -> Result<(), EmulationError> {
+        let frame = self.state.frames;
+        while self.state.frames == frame {
+            self.step()?;
+        }
+
+        Ok(())
+    }
+
+    /// Executes a single instruction returning the [Opcode] which was executed. The
+    /// timers are decremented once every `tick_rate` instructions.
+    pub fn step(&mut self) -> Result<Opcode, EmulationError> {
+        let pc = self.state.program_counter;
+        let error = |fault, opcode| EmulationError {
+            fault,
+            pc,
+            opcode,
+            cycle: self.state.cycles,
+        };
+
+        let word = self
+            .state
+            .memory
+            .fetch_instruction(pc)
+            .map_err(|fault| error(fault, None))?;
+
+        let opcode = Opcode::decode(&word.to_be_bytes())
+            .ok_or_else(|| error(Fault::InvalidInstruction, Some(word)))?;
+
+        #[cfg(feature = "std")]
+        let pending = self
+            .rewind
+            .as_ref()
+            .and_then(|rewind| rewind.capture(&self.state, opcode));
+
+        self.state.program_counter = pc.wrapping_add(2);
+        if let Err(fault) = self.execute(opcode) {
+            // Leave the program counter on the faulting instruction.
+            self.state.program_counter = pc;
+            return Err(EmulationError {
+                fault,
+                pc,
+                opcode: Some(word),
+                cycle: self.state.cycles,
+            });
+        }
+
+        #[cfg(feature = "std")]
+        if let (Some(rewind), Some(pending)) = (&mut self.rewind, pending) {
+            rewind.commit(pending);
+        }
+
+        self.state.cycles += 1;
+        self.state.frame_cycles += 1;
+
+        let vblank_wait = self.quirks.display_wait && matches!(opcode, Opcode::Drw(..));
+        if self.state.frame_cycles >= self.tick_rate || vblank_wait {
+            self.end_frame();
+        }
+
+        Ok(opcode)
+    }
+
+    fn end_frame(&mut self) {
+        self.buzzer = self.state.sound_register > 0;
+        self.state.delay_register = self.state.delay_register.saturating_sub(1);
+        self.state.sound_register = self.state.sound_register.saturating_sub(1);
+        self.state.frame_cycles = 0;
+        self.state.frames += 1;
+    }
+
+    fn execute(&mut self, opcode: Opcode) -> Result<(), Fault> {
+        use Opcode::*;
+
+        let state = &mut self.state;
+        let vf = Register::VF;
+
+        match opcode {
+            Sys(_) => {}
+            Cls => state.framebuffer.clear(),
+            Ret => state.program_counter = state.stack.pop()?,
+            Jp(addr) => state.program_counter = addr,
+            Call(addr) => {
+                state.stack.push(state.program_counter)?;
+                state.program_counter = addr;
+            }
+
+            Se(r, x) => state.skip_if(state.registers.get(r) == x),
+            Sne(r, x) => state.skip_if(state.registers.get(r) != x),
+            Sev(r1, r2) => state.skip_if(state.registers.get(r1) == state.registers.get(r2)),
+            LdImm(r, x) => state.registers.set(r, x),
+            AddImm(r, x) => state
+                .registers
+                .set(r, state.registers.get(r).wrapping_add(x)),
+            Ld(r1, r2) => state.registers.set(r1, state.registers.get(r2)),
+
+            Or(r1, r2) | And(r1, r2) | Xor(r1, r2) => {
+                let (a, b) = (state.registers.get(r1), state.registers.get(r2));
+                let result = match opcode {
+                    Or(..) => a | b,
+                    And(..) => a & b,
+                    _ => a ^ b,
+                };
+
+                state.registers.set(r1, result);
+                if self.quirks.vf_reset {
+                    state.registers.set(vf, 0);
+                }
+            }
+
+            Add(r1, r2) => {
+                let (result, carry) = state
+                    .registers
+                    .get(r1)
+                    .overflowing_add(state.registers.get(r2));
+                state.registers.set(r1, result);
+                state.registers.set(vf, carry as u8);
+            }
+
+            Sub(r1, r2) => {
+                let (result, borrow) = state
+                    .registers
+                    .get(r1)
+                    .overflowing_sub(state.registers.get(r2));
+                state.registers.set(r1, result);
+                state.registers.set(vf, !borrow as u8);
+            }
+
+            Subn(r1, r2) => {
+                let (result, borrow) = state
+                    .registers
+                    .get(r2)
+                    .overflowing_sub(state.registers.get(r1));
+                state.registers.set(r1, result);
+                state.registers.set(vf, !borrow as u8);
+            }
+
+            Shr(r1, r2) => {
+                let value = state
+                    .registers
+                    .get(if self.quirks.shift_uses_vy { r2 } else { r1 });
+                state.registers.set(r1, value >> 1);
+                state.registers.set(vf, value & 0x01);
+            }
+
+            Shl(r1, r2) => {
+                let value = state
+                    .registers
+                    .get(if self.quirks.shift_uses_vy { r2 } else { r1 });
+                state.registers.set(r1, value << 1);
+                state.registers.set(vf, value >> 7);
+            }
+
+            Snev(r1, r2) => state.skip_if(state.registers.get(r1) != state.registers.get(r2)),
+            Ldi(addr) => state.address_register = addr,
+
+            JpV0(addr) => {
+                let offset = if self.quirks.jump_uses_vx {
+                    state.registers.0[(addr >> 8) as usize & 0x0F]
+                } else {
+                    state.registers.0[0]
+                };
+
+                state.program_counter = addr + offset as u16;
+            }
+
+            Rnd(r, x) => {
+                let value = state.rng.next_u8() & x;
+                state.registers.set(r, value);
+            }
+
+            Drw(r1, r2, n) => {
+                let x = state.registers.get(r1) as usize;
+                let y = state.registers.get(r2) as usize;
+                let sprite = state
+                    .memory
+                    .read(state.address_register as usize, n.as_usize())?;
+
+                let collision =
+                    state
+                        .framebuffer
+                        .draw_sprite(x, y, sprite, self.quirks.clip_sprites);
+
+                state.registers.set(vf, collision as u8);
+            }
+
+            Skp(r) => state.skip_if(state.keypad.is_pressed(state.registers.get(r))),
+            Sknp(r) => state.skip_if(!state.keypad.is_pressed(state.registers.get(r))),
+            LdVDt(r) => state.registers.set(r, state.delay_register),
+
+            LdK(r) => match state.keypad.first_pressed() {
+                Some(key) => state.registers.set(r, key),
+                None => state.program_counter -= 2,
+            },
+
+            LdDtV(r) => state.delay_register = state.registers.get(r),
+            LdStV(r) => state.sound_register = state.registers.get(r),
+
+            AddI(r) => {
+                state.address_register = state
+                    .address_register
+                    .wrapping_add(state.registers.get(r) as u16);
+            }
+
+            LdF(r) => {
+                let digit = (state.registers.get(r) & 0x0F) as u16;
+                state.address_register = FONT_ADDRESS + digit * 5;
+            }
+
+            LdB(r) => {
+                let value = state.registers.get(r);
+                let digits = [value / 100, value / 10 % 10, value % 10];
+                state
+                    .memory
+                    .load(state.address_register as usize, &digits)?;
+            }
+
+            Dump(r) => {
+                let count = r.0.as_usize() + 1;
+                let registers = state.registers.0;
+                state
+                    .memory
+                    .load(state.address_register as usize, &registers[..count])?;
+
+                if self.quirks.memory_increments_i {
+                    state.address_register += count as u16;
+                }
+            }
+
+            Restore(r) => {
+                let count = r.0.as_usize() + 1;
+                let values = state.memory.read(state.address_register as usize, count)?;
+                state.registers.0[..count].copy_from_slice(values);
+
+                if self.quirks.memory_increments_i {
+                    state.address_register += count as u16;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the current state of the keypad.
+    pub fn keypad(&self) -> Keypad {
+        self.state.keypad
+    }
+
+    /// Sets which keys on the keypad are held down.
+    pub fn set_keypad(&mut self, keypad: Keypad) {
+        self.state.keypad = keypad;
+    }
+
+    /// Returns the values of the general purpose registers V0 through VF.
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.state.registers.0
+    }
+
+    /// Returns the value of the address register I.
+    pub fn address_register(&self) -> u16 {
+        self.state.address_register
+    }
+
+    /// Returns the address of the next instruction to be executed.
+    pub fn program_counter(&self) -> u16 {
+        self.state.program_counter
+    }
+
+    /// Returns the number of return addresses on the stack.
+    pub fn stack_depth(&self) -> usize {
+        self.state.stack.len()
+    }
+
+    /// Returns the value of the delay timer.
+    pub fn delay_timer(&self) -> u8 {
+        self.state.delay_register
+    }
+
+    /// Returns the value of the sound timer.
+    pub fn sound_timer(&self) -> u8 {
+        self.state.sound_register
+    }
+
+    /// Returns whether the buzzer sounded during the last completed frame, which is the
+    /// case whenever the sound timer was non-zero when it was decremented.
+    pub fn buzzer(&self) -> bool {
+        self.buzzer
+    }
+
+    /// Returns the number of instructions executed since the program was loaded.
+    pub fn cycles(&self) -> u64 {
+        self.state.cycles
+    }
+
+    /// Returns the number of 60 Hz frames elapsed since the program was loaded.
+    pub fn frames(&self) -> u64 {
+        self.state.frames
+    }
+
+    /// Returns the contents of RAM.
+    pub fn memory(&self) -> &[u8] {
+        &self.state.memory.0
+    }
+
+    /// Returns the contents of the display.
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.state.framebuffer
+    }
+
+    /// Returns the quirks used when executing instructions.
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Returns the number of instructions executed per 60 Hz frame.
+    pub fn tick_rate(&self) -> u32 {
+        self.tick_rate
+    }
+
+    /// Returns the number of nested subroutine calls the stack can hold.
+    pub fn stack_capacity(&self) -> usize {
+        self.stack_depth
+            .unwrap_or_else(|| default_stack_depth(self.quirks))
+    }
+
+    /// Returns whether `CALL` faults or wraps around when the stack is full.
+    pub fn stack_overflow(&self) -> OverflowMode {
+        self.stack_overflow
+    }
+}
+
+impl EmulatorState {
+    /// Skips the next instruction if `condition` is true.
+    #[inline]
+    fn skip_if(&mut self, condition: bool) {
+        if condition {
+            self.program_counter = self.program_counter.wrapping_add(2);
+        }
     }
 }
 
@@ -0,0 +1,305 @@
+use std::collections::VecDeque;
+
+use super::{Emulator, EmulatorState, Keypad, Registers, Rng, Stack};
+use crate::{data::Addr, framebuffer::Framebuffer, opcode::Opcode};
+
+/// Largest number of instructions recorded between full snapshots.
+const SNAPSHOT_INTERVAL: usize = 1024;
+
+/// [RewindBuffer] records the history of an emulator so that execution can be stepped
+/// backwards. History is kept as a ring of segments, each starting with a full snapshot
+/// of the machine followed by one [Delta] per executed instruction. Single steps undo
+/// one delta while longer jumps restore snapshots directly. Once more than `capacity`
+/// instructions are recorded the oldest segment is discarded.
+pub struct RewindBuffer {
+    capacity: usize,
+    interval: usize,
+    len: usize,
+    segments: VecDeque<Segment>,
+}
+
+struct Segment {
+    snapshot: Box<EmulatorState>,
+    deltas: Vec<Delta>,
+}
+
+/// [Pending] is the history of an instruction which is being executed, captured before
+/// it runs and only committed to the buffer once it has executed successfully.
+pub(super) struct Pending {
+    snapshot: Option<Box<EmulatorState>>,
+    delta: Delta,
+}
+
+/// [Delta] holds everything needed to undo a single instruction: the small scalar parts
+/// of the machine plus whatever memory or display contents the instruction overwrites.
+struct Delta {
+    registers: Registers,
+    address_register: u16,
+    program_counter: u16,
+    delay_register: u8,
+    sound_register: u8,
+    stack: Stack,
+    keypad: Keypad,
+    rng: Rng,
+    cycles: u64,
+    frames: u64,
+    frame_cycles: u32,
+    memory: Vec<(Addr, u8)>,
+    framebuffer: Option<Framebuffer>,
+}
+
+impl Emulator {
+    /// Records up to `capacity` instructions of history so that execution can be
+    /// stepped backwards with [Emulator::step_back] and [Emulator::reverse_continue].
+    pub fn with_rewind(self, capacity: usize) -> Self {
+        Emulator {
+            rewind: Some(RewindBuffer::new(capacity)),
+            ..self
+        }
+    }
+
+    /// Undoes the most recently executed instruction. Returns false if there is no
+    /// recorded history to step back into.
+    pub fn step_back(&mut self) -> bool {
+        self.rewind(1) == 1
+    }
+
+    /// Undoes up to `count` instructions returning the number actually undone.
+    pub fn rewind(&mut self, count: usize) -> usize {
+        match &mut self.rewind {
+            Some(rewind) => rewind.rewind(&mut self.state, count),
+            None => 0,
+        }
+    }
+
+    /// Steps backwards until the program counter reaches one of `breakpoints` or the
+    /// recorded history runs out. Returns true if a breakpoint was reached.
+    pub fn reverse_continue(&mut self, breakpoints: &[Addr]) -> bool {
+        while self.step_back() {
+            if breakpoints.contains(&self.state.program_counter) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Returns the number of instructions which can currently be stepped back over.
+    pub fn history_len(&self) -> usize {
+        self.rewind.as_ref().map_or(0, |rewind| rewind.len())
+    }
+}
+
+impl RewindBuffer {
+    /// Constructs an empty buffer holding up to `capacity` instructions of history.
+    pub fn new(capacity: usize) -> Self {
+        RewindBuffer {
+            capacity,
+            interval: (capacity / 8).clamp(1, SNAPSHOT_INTERVAL),
+            len: 0,
+            segments: VecDeque::new(),
+        }
+    }
+
+    /// Returns the number of instructions which can currently be undone.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Discards all recorded history.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.segments.clear();
+    }
+
+    /// Captures the state of the machine right before `opcode` is executed. Returns
+    /// None if the buffer records no history.
+    pub(super) fn capture(&self, state: &EmulatorState, opcode: Opcode) -> Option<Pending> {
+        if self.capacity == 0 {
+            return None;
+        }
+
+        let needs_snapshot = self
+            .segments
+            .back()
+            .is_none_or(|segment| segment.deltas.len() >= self.interval);
+
+        Some(Pending {
+            snapshot: needs_snapshot.then(|| Box::new(state.clone())),
+            delta: Delta::capture(state, opcode),
+        })
+    }
+
+    /// Records an instruction captured by [RewindBuffer::capture] which has executed.
+    pub(super) fn commit(&mut self, pending: Pending) {
+        if let Some(snapshot) = pending.snapshot {
+            self.segments.push_back(Segment {
+                snapshot,
+                deltas: Vec::with_capacity(self.interval),
+            });
+        }
+
+        let segment = self.segments.back_mut().unwrap();
+        segment.deltas.push(pending.delta);
+        self.len += 1;
+
+        while self.len > self.capacity && self.segments.len() > 1 {
+            let segment = self.segments.pop_front().unwrap();
+            self.len -= segment.deltas.len();
+        }
+    }
+
+    /// Undoes up to `count` instructions, returning the number actually undone.
+    pub(super) fn rewind(&mut self, state: &mut EmulatorState, count: usize) -> usize {
+        let mut remaining = count;
+        while remaining > 0 {
+            let segment = match self.segments.back_mut() {
+                Some(segment) => segment,
+                None => break,
+            };
+
+            if remaining >= segment.deltas.len() {
+                // The whole segment is being undone so jump straight to its snapshot.
+                let segment = self.segments.pop_back().unwrap();
+                remaining -= segment.deltas.len();
+                self.len -= segment.deltas.len();
+                *state = *segment.snapshot;
+            } else {
+                let delta = segment.deltas.pop().unwrap();
+                delta.restore(state);
+                remaining -= 1;
+                self.len -= 1;
+            }
+        }
+
+        count - remaining
+    }
+}
+
+impl Delta {
+    fn capture(state: &EmulatorState, opcode: Opcode) -> Self {
+        let i = state.address_register;
+        let written = match opcode {
+            Opcode::LdB(_) => 3,
+            Opcode::Dump(r) => r.0.as_usize() + 1,
+            _ => 0,
+        };
+
+        let memory = (i..i.saturating_add(written as u16))
+            .filter_map(|addr| state.memory.0.get(addr as usize).map(|&b| (addr, b)))
+            .collect();
+
+        let framebuffer = match opcode {
+            Opcode::Cls | Opcode::Drw(..) => Some(state.framebuffer),
+            _ => None,
+        };
+
+        Delta {
+            registers: state.registers.clone(),
+            address_register: state.address_register,
+            program_counter: state.program_counter,
+            delay_register: state.delay_register,
+            sound_register: state.sound_register,
+            stack: state.stack.clone(),
+            keypad: state.keypad,
+            rng: state.rng,
+            cycles: state.cycles,
+            frames: state.frames,
+            frame_cycles: state.frame_cycles,
+            memory,
+            framebuffer,
+        }
+    }
+
+    fn restore(self, state: &mut EmulatorState) {
+        state.registers = self.registers;
+        state.address_register = self.address_register;
+        state.program_counter = self.program_counter;
+        state.delay_register = self.delay_register;
+        state.sound_register = self.sound_register;
+        state.stack = self.stack;
+        state.keypad = self.keypad;
+        state.rng = self.rng;
+        state.cycles = self.cycles;
+        state.frames = self.frames;
+        state.frame_cycles = self.frame_cycles;
+
+        for (addr, value) in self.memory {
+            state.memory.0[addr as usize] = value;
+        }
+
+        if let Some(framebuffer) = self.framebuffer {
+            state.framebuffer = framebuffer;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::emulation::Emulator;
+
+    // LD V0, 0x00; ADD V0, 0x01; LD I, 0x300; LD B, V0; DRW V0, V0, 1; JP 0x202
+    const PROGRAM: [u8; 12] = [
+        0x60, 0x00, 0x70, 0x01, 0xA3, 0x00, 0xF0, 0x33, 0xD0, 0x01, 0x12, 0x02,
+    ];
+
+    fn emulator(history: usize) -> Emulator {
+        let mut emulator = Emulator::new().with_rewind(history);
+        emulator.load(&PROGRAM).unwrap();
+        emulator
+    }
+
+    #[test]
+    fn step_back_restores_state() {
+        let mut emulator = emulator(10_000);
+        let mut states = Vec::new();
+        for _ in 0..3000 {
+            states.push(emulator.save_state());
+            emulator.step().unwrap();
+        }
+
+        assert!(emulator.step_back());
+        assert_eq!(emulator.save_state(), states[2999]);
+
+        assert_eq!(emulator.rewind(1500), 1500);
+        assert_eq!(emulator.save_state(), states[1499]);
+
+        assert_eq!(emulator.rewind(usize::MAX), 1499);
+        assert_eq!(emulator.save_state(), states[0]);
+        assert!(!emulator.step_back());
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let mut emulator = emulator(100);
+        for _ in 0..1000 {
+            emulator.step().unwrap();
+        }
+
+        assert!(emulator.history_len() <= 100);
+        assert!(emulator.history_len() >= 100 - 100 / 8);
+    }
+
+    #[test]
+    fn reverse_continue_stops_at_breakpoint() {
+        let mut emulator = emulator(1000);
+        for _ in 0..50 {
+            emulator.step().unwrap();
+        }
+
+        assert!(emulator.reverse_continue(&[0x206]));
+        assert_eq!(emulator.program_counter(), 0x206);
+        assert!(emulator.cycles() < 50);
+    }
+
+    #[test]
+    fn faults_are_not_recorded() {
+        // RET with an empty stack.
+        let mut emulator = Emulator::new().with_rewind(100);
+        emulator.load(&[0x00, 0xEE]).unwrap();
+
+        assert!(emulator.step().is_err());
+        assert_eq!(emulator.history_len(), 0);
+        assert!(!emulator.step_back());
+    }
+}
@@ -0,0 +1,342 @@
+use std::fmt;
+
+use super::{
+    Emulator, EmulatorState, Keypad, Memory, OverflowMode, Registers, Rng, Stack, MEMORY_SIZE,
+};
+use crate::{
+    checksum::crc32,
+    framebuffer::{Framebuffer, HEIGHT},
+    quirks::Quirks,
+};
+
+/// Magic bytes at the start of every save state.
+const MAGIC: &[u8; 4] = b"C8SS";
+
+/// Version of the save state layout written by [Emulator::save_state].
+const VERSION: u16 = 3;
+
+/// Size of the header: magic, version, payload length and payload checksum.
+const HEADER_SIZE: usize = 4 + 2 + 4 + 4;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SaveStateError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    ChecksumMismatch,
+    Truncated,
+    TrailingData,
+}
+
+impl fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveStateError::BadMagic => write!(f, "not a save state"),
+            SaveStateError::UnsupportedVersion(version) => {
+                write!(f, "unsupported save state version {}", version)
+            }
+            SaveStateError::ChecksumMismatch => write!(f, "save state is corrupt"),
+            SaveStateError::Truncated => write!(f, "save state is truncated"),
+            SaveStateError::TrailingData => write!(f, "unexpected data after the save state"),
+        }
+    }
+}
+
+impl std::error::Error for SaveStateError {}
+
+impl Emulator {
+    /// Serializes the full machine state, including quirks, tick rate and stack depth, into a
+    /// versioned binary blob which can be restored with [Emulator::load_state].
+    ///
+    /// The blob starts with the magic bytes `C8SS`, a big-endian version number, and the
+    /// length and CRC-32 of the payload which follows.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut payload = Vec::new();
+        let state = &self.state;
+
+        payload.extend_from_slice(&state.registers.0);
+        payload.extend_from_slice(&state.address_register.to_be_bytes());
+        payload.extend_from_slice(&state.program_counter.to_be_bytes());
+        payload.push(state.delay_register);
+        payload.push(state.sound_register);
+
+        let stack = &state.stack;
+        payload.push(stack.depth as u8);
+        payload.push((stack.overflow == OverflowMode::Wrap) as u8);
+        payload.push(stack.top as u8);
+        payload.push(stack.len as u8);
+        for addr in &state.stack.memory {
+            payload.extend_from_slice(&addr.to_be_bytes());
+        }
+
+        payload.extend_from_slice(&state.memory.0);
+        for row in state.framebuffer.rows() {
+            payload.extend_from_slice(&row.to_be_bytes());
+        }
+
+        payload.extend_from_slice(&state.keypad.0.to_be_bytes());
+        payload.extend_from_slice(&state.rng.0.to_be_bytes());
+        payload.push(encode_quirks(&self.quirks));
+        payload.extend_from_slice(&self.tick_rate.to_be_bytes());
+        payload.extend_from_slice(&state.cycles.to_be_bytes());
+        payload.extend_from_slice(&state.frames.to_be_bytes());
+        payload.extend_from_slice(&state.frame_cycles.to_be_bytes());
+        payload.push(self.buzzer as u8);
+
+        let mut data = Vec::with_capacity(HEADER_SIZE + payload.len());
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&VERSION.to_be_bytes());
+        data.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        data.extend_from_slice(&crc32(&payload).to_be_bytes());
+        data.extend_from_slice(&payload);
+        data
+    }
+
+    /// Restores a machine state previously produced by [Emulator::save_state], discarding
+    /// any rewind history. The emulator is left untouched if the save state cannot be
+    /// read.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), SaveStateError> {
+        let mut header = Reader(data);
+        if header.take(4)? != MAGIC {
+            return Err(SaveStateError::BadMagic);
+        }
+
+        let version = header.u16()?;
+        if version != VERSION {
+            return Err(SaveStateError::UnsupportedVersion(version));
+        }
+
+        let len = header.u32()? as usize;
+        let checksum = header.u32()?;
+        if header.0.len() < len {
+            return Err(SaveStateError::Truncated);
+        } else if header.0.len() > len {
+            return Err(SaveStateError::TrailingData);
+        }
+
+        if crc32(header.0) != checksum {
+            return Err(SaveStateError::ChecksumMismatch);
+        }
+
+        let mut r = header;
+        let registers = Registers(r.take(16)?.try_into().unwrap());
+        let address_register = r.u16()?;
+        let program_counter = r.u16()?;
+        let delay_register = r.u8()?;
+        let sound_register = r.u8()?;
+
+        let overflow = |wrap| match wrap {
+            0 => OverflowMode::Error,
+            _ => OverflowMode::Wrap,
+        };
+
+        let mut stack = Stack::new(r.u8()? as usize, overflow(r.u8()?));
+        stack.top = (r.u8()? as usize) % stack.depth;
+        stack.len = (r.u8()? as usize).min(stack.depth);
+
+        for addr in stack.memory.iter_mut() {
+            *addr = r.u16()?;
+        }
+
+        let memory = Memory(r.take(MEMORY_SIZE)?.try_into().unwrap());
+        let mut rows = [0; HEIGHT];
+        for row in rows.iter_mut() {
+            *row = r.u64()?;
+        }
+
+        let keypad = Keypad(r.u16()?);
+        let rng = Rng(r.u32()?);
+        let quirks = decode_quirks(r.u8()?);
+        let tick_rate = r.u32()?;
+        let cycles = r.u64()?;
+        let frames = r.u64()?;
+        let frame_cycles = r.u32()?;
+        let buzzer = r.u8()? != 0;
+        if !r.0.is_empty() {
+            return Err(SaveStateError::TrailingData);
+        }
+
+        self.state = EmulatorState {
+            registers,
+            address_register,
+            program_counter,
+            delay_register,
+            sound_register,
+            stack,
+            memory,
+            framebuffer: Framebuffer::from_rows(rows),
+            keypad,
+            rng,
+            cycles,
+            frames,
+            frame_cycles,
+        };
+
+        self.quirks = quirks;
+        self.tick_rate = tick_rate.max(1);
+        self.stack_depth = Some(self.state.stack.depth);
+        self.stack_overflow = self.state.stack.overflow;
+        self.buzzer = buzzer;
+        if let Some(rewind) = &mut self.rewind {
+            rewind.clear();
+        }
+
+        Ok(())
+    }
+}
+
+fn encode_quirks(quirks: &Quirks) -> u8 {
+    quirks
+        .flags()
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (i, &set)| bits | ((set as u8) << i))
+}
+
+fn decode_quirks(bits: u8) -> Quirks {
+    let mut flags = [false; 6];
+    for (i, flag) in flags.iter_mut().enumerate() {
+        *flag = bits & (1 << i) != 0;
+    }
+
+    Quirks::from_flags(flags)
+}
+
+/// [Reader] consumes big-endian values from the front of a byte slice.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SaveStateError> {
+        if self.0.len() < len {
+            return Err(SaveStateError::Truncated);
+        }
+
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, SaveStateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, SaveStateError> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, SaveStateError> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, SaveStateError> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::quirks::Platform;
+
+    fn emulator() -> Emulator {
+        // LD V0, 0x2A; CALL 0x206; JP 0x204; RND V1, 0xFF; DRW V0, V0, 5; JP 0x20A
+        let program = [
+            0x60, 0x2A, 0x22, 0x06, 0x12, 0x04, 0xC1, 0xFF, 0xD0, 0x05, 0x12, 0x0A,
+        ];
+
+        let mut emulator = Emulator::new()
+            .with_quirks(Platform::Schip.quirks())
+            .with_stack_depth(Platform::Vip.stack_depth())
+            .with_stack_overflow(OverflowMode::Wrap)
+            .with_seed(7);
+
+        emulator.load(&program).unwrap();
+        for _ in 0..25 {
+            emulator.step().unwrap();
+        }
+
+        emulator
+    }
+
+    #[test]
+    fn round_trip() {
+        let original = emulator();
+        let data = original.save_state();
+
+        let mut restored = Emulator::new();
+        restored.load_state(&data).unwrap();
+        assert_eq!(restored.save_state(), data);
+        assert_eq!(restored.quirks, Platform::Schip.quirks());
+        assert_eq!(restored.stack_depth(), 1);
+        assert_eq!(restored.stack_depth, Some(12));
+        assert_eq!(restored.stack_overflow, OverflowMode::Wrap);
+    }
+
+    #[test]
+    fn rejects_corrupt_state() {
+        let mut data = emulator().save_state();
+        let mut emulator = Emulator::new();
+
+        assert_eq!(
+            emulator.load_state(&data[..100]),
+            Err(SaveStateError::Truncated)
+        );
+
+        let mut longer = data.clone();
+        longer.push(0);
+        assert_eq!(
+            emulator.load_state(&longer),
+            Err(SaveStateError::TrailingData)
+        );
+
+        data[HEADER_SIZE + 3] ^= 0xFF;
+        assert_eq!(
+            emulator.load_state(&data),
+            Err(SaveStateError::ChecksumMismatch)
+        );
+
+        data[0] = b'X';
+        assert_eq!(emulator.load_state(&data), Err(SaveStateError::BadMagic));
+    }
+
+    /// Wraps a payload in a save state header with a matching length and checksum.
+    fn with_header(payload: &[u8]) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&VERSION.to_be_bytes());
+        data.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        data.extend_from_slice(&crc32(payload).to_be_bytes());
+        data.extend_from_slice(payload);
+        data
+    }
+
+    #[test]
+    fn rejects_payload_of_wrong_size() {
+        let payload = &emulator().save_state()[HEADER_SIZE..];
+        let mut emulator = Emulator::new();
+
+        let short = with_header(&payload[..payload.len() - 1]);
+        assert_eq!(emulator.load_state(&short), Err(SaveStateError::Truncated));
+
+        let long = with_header(&[payload, &[0]].concat());
+        assert_eq!(
+            emulator.load_state(&long),
+            Err(SaveStateError::TrailingData)
+        );
+
+        assert_eq!(emulator.load_state(&with_header(payload)), Ok(()));
+    }
+
+    #[test]
+    fn restores_buzzer() {
+        // LD V0, 0x05; LD ST, V0; JP 0x204
+        let mut original = Emulator::new();
+        original
+            .load(&[0x60, 0x05, 0xF0, 0x18, 0x12, 0x04])
+            .unwrap();
+        original.run_frame().unwrap();
+        assert!(original.buzzer());
+
+        let mut restored = Emulator::new();
+        restored.load_state(&original.save_state()).unwrap();
+        assert!(restored.buzzer());
+    }
+}
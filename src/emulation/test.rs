@@ -0,0 +1,540 @@
+//! Conformance tests pinning down the effect of every [Opcode] on the emulator state
+//! under each of the quirks presets.
+
+use super::*;
+use crate::quirks::Platform;
+
+const PLATFORMS: [Platform; 3] = [Platform::Vip, Platform::Schip, Platform::XoChip];
+
+/// Constructs an emulator for `platform` with a program made up of 16-bit instructions
+/// loaded at 0x200.
+fn load(platform: Platform, instructions: &[u16]) -> Emulator {
+    let program: Vec<u8> = instructions.iter().flat_map(|i| i.to_be_bytes()).collect();
+    let mut emulator = Emulator::new()
+        .with_quirks(platform.quirks())
+        .with_stack_depth(platform.stack_depth());
+    emulator.load(&program).unwrap();
+    emulator
+}
+
+/// Executes `count` instructions.
+fn run(emulator: &mut Emulator, count: usize) {
+    for _ in 0..count {
+        emulator.step().unwrap();
+    }
+}
+
+impl Emulator {
+    /// Steps until an instruction faults.
+    fn step_until_fault(&mut self) -> EmulationError {
+        loop {
+            if let Err(err) = self.step() {
+                return err;
+            }
+        }
+    }
+}
+
+fn v(emulator: &Emulator, index: u8) -> u8 {
+    emulator.state.registers.0[index as usize]
+}
+
+/// Runs a two instruction program loading `a` into V1 and `b` into V2 followed by
+/// `instruction`, returning V1 and VF.
+fn alu(platform: Platform, a: u8, b: u8, instruction: u16) -> (u8, u8) {
+    let mut emulator = load(
+        platform,
+        &[0x6100 | a as u16, 0x6200 | b as u16, 0x6F55, instruction],
+    );
+    run(&mut emulator, 4);
+    (v(&emulator, 1), v(&emulator, 0xF))
+}
+
+#[test]
+fn sys_is_ignored() {
+    for platform in PLATFORMS {
+        let mut emulator = load(platform, &[0x0123]);
+        let before = emulator.state.clone();
+        run(&mut emulator, 1);
+
+        assert_eq!(emulator.program_counter(), 0x202);
+        assert_eq!(emulator.state.registers.0, before.registers.0);
+        assert_eq!(emulator.address_register(), before.address_register);
+    }
+}
+
+#[test]
+fn cls_clears_display() {
+    for platform in PLATFORMS {
+        // LD I, font; DRW V0, V0, 5; CLS
+        let mut emulator = load(platform, &[0xA050, 0xD005, 0x00E0]);
+        run(&mut emulator, 2);
+        assert_ne!(*emulator.framebuffer(), Framebuffer::default());
+
+        run(&mut emulator, 1);
+        assert_eq!(*emulator.framebuffer(), Framebuffer::default());
+    }
+}
+
+#[test]
+fn jp_sets_pc() {
+    for platform in PLATFORMS {
+        let mut emulator = load(platform, &[0x1ABC]);
+        run(&mut emulator, 1);
+        assert_eq!(emulator.program_counter(), 0xABC);
+    }
+}
+
+#[test]
+fn call_and_ret() {
+    for platform in PLATFORMS {
+        // CALL 0x206; JP 0x202; (unused); RET
+        let mut emulator = load(platform, &[0x2206, 0x1202, 0x0000, 0x00EE]);
+        run(&mut emulator, 1);
+        assert_eq!(emulator.program_counter(), 0x206);
+        assert_eq!(emulator.stack_depth(), 1);
+
+        run(&mut emulator, 1);
+        assert_eq!(emulator.program_counter(), 0x202);
+        assert_eq!(emulator.stack_depth(), 0);
+    }
+}
+
+#[test]
+fn ret_on_empty_stack_underflows() {
+    for platform in PLATFORMS {
+        let mut emulator = load(platform, &[0x00EE]);
+        let err = emulator.step().unwrap_err();
+        assert_eq!(err.fault, Fault::StackUnderflow);
+        assert_eq!((err.pc, err.opcode, err.cycle), (0x200, Some(0x00EE), 0));
+        assert_eq!(emulator.program_counter(), 0x200);
+    }
+}
+
+#[test]
+fn nested_calls_overflow() {
+    for platform in PLATFORMS {
+        // CALL 0x200
+        let mut emulator = load(platform, &[0x2200]);
+        run(&mut emulator, platform.stack_depth());
+        assert_eq!(emulator.stack_depth(), platform.stack_depth());
+
+        let err = emulator.step().unwrap_err();
+        assert_eq!(err.fault, Fault::StackOverflow);
+        assert_eq!(err.cycle, platform.stack_depth() as u64);
+    }
+}
+
+#[test]
+fn stack_depth_follows_quirks() {
+    let depth = |emulator: Emulator| {
+        // CALL 0x200
+        let mut emulator = emulator;
+        emulator.load(&[0x22, 0x00]).unwrap();
+        emulator.step_until_fault().cycle as usize
+    };
+
+    assert_eq!(depth(Emulator::new()), Platform::Vip.stack_depth());
+    for platform in PLATFORMS {
+        let emulator = Emulator::new().with_quirks(platform.quirks());
+        assert_eq!(depth(emulator), platform.stack_depth());
+    }
+
+    let custom = Quirks {
+        display_wait: false,
+        ..Platform::Vip.quirks()
+    };
+    assert_eq!(depth(Emulator::new().with_quirks(custom)), MAX_STACK_DEPTH);
+
+    let emulator = Emulator::new()
+        .with_quirks(Platform::Schip.quirks())
+        .with_stack_depth(3);
+    assert_eq!(depth(emulator), 3);
+}
+
+#[test]
+fn stack_wraps_on_overflow() {
+    let mut stack = Stack::new(2, OverflowMode::Wrap);
+    for addr in [0x202, 0x204, 0x206] {
+        stack.push(addr).unwrap();
+    }
+
+    // The third push overwrote the oldest address.
+    assert_eq!(stack.len(), 2);
+    assert_eq!(stack.pop(), Ok(0x206));
+    assert_eq!(stack.pop(), Ok(0x204));
+    assert_eq!(stack.pop(), Err(Fault::StackUnderflow));
+}
+
+#[test]
+fn conditional_skips() {
+    // Each case loads V1 = 0x12 and V2 = 0x12 or 0x34 before the instruction under test.
+    let cases = [
+        (0x3112, 0x12, true),
+        (0x3113, 0x12, false),
+        (0x4112, 0x12, false),
+        (0x4113, 0x12, true),
+        (0x5120, 0x12, true),
+        (0x5120, 0x34, false),
+        (0x9120, 0x12, false),
+        (0x9120, 0x34, true),
+    ];
+
+    for platform in PLATFORMS {
+        for (instruction, v2, skips) in cases {
+            let mut emulator = load(platform, &[0x6112, 0x6200 | v2, instruction]);
+            run(&mut emulator, 3);
+
+            let expected = if skips { 0x208 } else { 0x206 };
+            assert_eq!(emulator.program_counter(), expected, "{:04X}", instruction);
+        }
+    }
+}
+
+#[test]
+fn load_and_add_immediate() {
+    for platform in PLATFORMS {
+        // LD VF, 0x07; LD V3, 0xFE; ADD V3, 0x03
+        let mut emulator = load(platform, &[0x6F07, 0x63FE, 0x7303]);
+        run(&mut emulator, 3);
+
+        assert_eq!(v(&emulator, 3), 0x01);
+        assert_eq!(v(&emulator, 0xF), 0x07, "ADD Vx, kk must not touch VF");
+    }
+}
+
+#[test]
+fn register_copy() {
+    for platform in PLATFORMS {
+        assert_eq!(alu(platform, 0x11, 0x22, 0x8120), (0x22, 0x55));
+    }
+}
+
+#[test]
+fn bitwise_operations() {
+    for platform in PLATFORMS {
+        let vf = if platform.quirks().vf_reset {
+            0x00
+        } else {
+            0x55
+        };
+        assert_eq!(alu(platform, 0b1100, 0b1010, 0x8121), (0b1110, vf));
+        assert_eq!(alu(platform, 0b1100, 0b1010, 0x8122), (0b1000, vf));
+        assert_eq!(alu(platform, 0b1100, 0b1010, 0x8123), (0b0110, vf));
+    }
+}
+
+#[test]
+fn add_sets_carry() {
+    for platform in PLATFORMS {
+        assert_eq!(alu(platform, 0x10, 0x20, 0x8124), (0x30, 0));
+        assert_eq!(alu(platform, 0xF0, 0x20, 0x8124), (0x10, 1));
+        assert_eq!(alu(platform, 0xFF, 0x01, 0x8124), (0x00, 1));
+    }
+}
+
+#[test]
+fn sub_sets_not_borrow() {
+    for platform in PLATFORMS {
+        assert_eq!(alu(platform, 0x30, 0x10, 0x8125), (0x20, 1));
+        assert_eq!(alu(platform, 0x10, 0x10, 0x8125), (0x00, 1));
+        assert_eq!(alu(platform, 0x10, 0x30, 0x8125), (0xE0, 0));
+
+        assert_eq!(alu(platform, 0x10, 0x30, 0x8127), (0x20, 1));
+        assert_eq!(alu(platform, 0x10, 0x10, 0x8127), (0x00, 1));
+        assert_eq!(alu(platform, 0x30, 0x10, 0x8127), (0xE0, 0));
+    }
+}
+
+#[test]
+fn shifts_set_shifted_out_bit() {
+    for platform in PLATFORMS {
+        // V1 = 0b1000_0001, V2 = 0b0100_0010
+        let uses_vy = platform.quirks().shift_uses_vy;
+        let shr = if uses_vy { (0x21, 0) } else { (0x40, 1) };
+        let shl = if uses_vy { (0x84, 0) } else { (0x02, 1) };
+
+        assert_eq!(alu(platform, 0x81, 0x42, 0x8126), shr);
+        assert_eq!(alu(platform, 0x81, 0x42, 0x812E), shl);
+    }
+}
+
+#[test]
+fn flag_wins_when_vf_is_the_destination() {
+    for platform in PLATFORMS {
+        // LD VF, 0xFF; LD V1, 0x01; ADD VF, V1
+        let mut emulator = load(platform, &[0x6FFF, 0x6101, 0x8F14]);
+        run(&mut emulator, 3);
+        assert_eq!(v(&emulator, 0xF), 1);
+
+        // LD VF, 0x01; LD V1, 0x02; SUB VF, V1
+        let mut emulator = load(platform, &[0x6F01, 0x6102, 0x8F15]);
+        run(&mut emulator, 3);
+        assert_eq!(v(&emulator, 0xF), 0);
+
+        // LD VF, 0x03; SHR VF, VF
+        let mut emulator = load(platform, &[0x6F03, 0x8FF6]);
+        run(&mut emulator, 2);
+        assert_eq!(v(&emulator, 0xF), 1);
+    }
+}
+
+#[test]
+fn load_address_register() {
+    for platform in PLATFORMS {
+        let mut emulator = load(platform, &[0xA123]);
+        run(&mut emulator, 1);
+        assert_eq!(emulator.address_register(), 0x123);
+    }
+}
+
+#[test]
+fn jump_with_offset() {
+    for platform in PLATFORMS {
+        // LD V0, 0x10; LD V3, 0x20; JP V0, 0x300
+        let mut emulator = load(platform, &[0x6010, 0x6320, 0xB300]);
+        run(&mut emulator, 3);
+
+        let expected = if platform.quirks().jump_uses_vx {
+            0x320
+        } else {
+            0x310
+        };
+        assert_eq!(emulator.program_counter(), expected);
+    }
+}
+
+#[test]
+fn random_is_masked_and_seeded() {
+    for platform in PLATFORMS {
+        let program = [0xC10F, 0xC2FF, 0xC300];
+        let mut a = load(platform, &program);
+        let mut b = load(platform, &program);
+        run(&mut a, 3);
+        run(&mut b, 3);
+
+        assert_eq!(v(&a, 1) & 0xF0, 0);
+        assert_eq!(v(&a, 3), 0);
+        assert_eq!(a.state.registers.0, b.state.registers.0);
+    }
+}
+
+#[test]
+fn draw_sets_collision() {
+    for platform in PLATFORMS {
+        // LD I, font; DRW V0, V0, 5; DRW V0, V0, 5
+        let mut emulator = load(platform, &[0xA050, 0xD005, 0xD005]);
+        run(&mut emulator, 2);
+        assert_eq!(v(&emulator, 0xF), 0);
+        assert!(emulator.framebuffer().pixel(0, 0));
+
+        run(&mut emulator, 1);
+        assert_eq!(v(&emulator, 0xF), 1);
+        assert_eq!(*emulator.framebuffer(), Framebuffer::default());
+    }
+}
+
+#[test]
+fn draw_clips_or_wraps_at_edges() {
+    for platform in PLATFORMS {
+        // LD I, font; LD V1, 62; LD V2, 30; DRW V1, V2, 5
+        let mut emulator = load(platform, &[0xA050, 0x613E, 0x621E, 0xD125]);
+        run(&mut emulator, 4);
+
+        let clip = platform.quirks().clip_sprites;
+        assert!(emulator.framebuffer().pixel(62, 30));
+        assert_eq!(emulator.framebuffer().pixel(0, 30), !clip);
+        assert_eq!(emulator.framebuffer().pixel(62, 0), !clip);
+    }
+}
+
+#[test]
+fn draw_starting_off_screen_wraps() {
+    for platform in PLATFORMS {
+        // LD I, font; LD V1, 64 + 1; LD V2, 32 + 2; DRW V1, V2, 1
+        let mut emulator = load(platform, &[0xA050, 0x6141, 0x6222, 0xD121]);
+        run(&mut emulator, 4);
+        assert!(emulator.framebuffer().pixel(1, 2));
+    }
+}
+
+#[test]
+fn draw_waits_for_vblank() {
+    for platform in PLATFORMS {
+        let mut emulator = load(platform, &[0xA050, 0xD005]);
+        run(&mut emulator, 2);
+
+        let frames = if platform.quirks().display_wait { 1 } else { 0 };
+        assert_eq!(emulator.frames(), frames);
+    }
+}
+
+#[test]
+fn key_skips() {
+    for platform in PLATFORMS {
+        // LD V1, 0x0A; SKP V1
+        let mut emulator = load(platform, &[0x610A, 0xE19E]);
+        emulator.set_keypad(Keypad::from_bits(1 << 0xA));
+        run(&mut emulator, 2);
+        assert_eq!(emulator.program_counter(), 0x206);
+
+        // LD V1, 0x0A; SKNP V1
+        let mut emulator = load(platform, &[0x610A, 0xE1A1]);
+        run(&mut emulator, 2);
+        assert_eq!(emulator.program_counter(), 0x206);
+
+        emulator.load(&[0x61, 0x0A, 0xE1, 0xA1]).unwrap();
+        emulator.set_keypad(Keypad::from_bits(1 << 0xA));
+        run(&mut emulator, 2);
+        assert_eq!(emulator.program_counter(), 0x204);
+    }
+}
+
+#[test]
+fn wait_for_key() {
+    for platform in PLATFORMS {
+        let mut emulator = load(platform, &[0xF30A]);
+        run(&mut emulator, 5);
+        assert_eq!(emulator.program_counter(), 0x200);
+
+        emulator.set_keypad(Keypad::from_bits(1 << 0x7));
+        run(&mut emulator, 1);
+        assert_eq!(emulator.program_counter(), 0x202);
+        assert_eq!(v(&emulator, 3), 0x7);
+    }
+}
+
+#[test]
+fn timers() {
+    for platform in PLATFORMS {
+        // LD V1, 0x05; LD DT, V1; LD ST, V1; LD V2, DT
+        let mut emulator = load(platform, &[0x6105, 0xF115, 0xF118, 0xF207]);
+        run(&mut emulator, 4);
+        assert_eq!(v(&emulator, 2), 5);
+        assert_eq!(emulator.delay_timer(), 5);
+        assert_eq!(emulator.sound_timer(), 5);
+
+        // Both timers count down once per frame.
+        let mut emulator = load(platform, &[0x6105, 0xF115, 0xF118, 0x1206]);
+        run(&mut emulator, DEFAULT_TICK_RATE as usize * 3);
+        assert_eq!(emulator.delay_timer(), 2);
+        assert_eq!(emulator.sound_timer(), 2);
+    }
+}
+
+#[test]
+fn add_to_address_register() {
+    for platform in PLATFORMS {
+        // LD I, 0xFFF; LD V1, 0x02; ADD I, V1
+        let mut emulator = load(platform, &[0xAFFF, 0x6102, 0xF11E]);
+        run(&mut emulator, 3);
+        assert_eq!(emulator.address_register(), 0x1001);
+        assert_eq!(v(&emulator, 0xF), 0);
+    }
+}
+
+#[test]
+fn font_sprite_address() {
+    for platform in PLATFORMS {
+        // LD V1, 0x1B; LD F, V1
+        let mut emulator = load(platform, &[0x611B, 0xF129]);
+        run(&mut emulator, 2);
+        assert_eq!(emulator.address_register(), FONT_ADDRESS + 0xB * 5);
+        assert_eq!(
+            emulator.memory()[0x050 + 0xB * 5..][..5],
+            [0xE0, 0x90, 0xE0, 0x90, 0xE0]
+        );
+    }
+}
+
+#[test]
+fn binary_coded_decimal() {
+    for platform in PLATFORMS {
+        // LD V1, 254; LD I, 0x300; LD B, V1
+        let mut emulator = load(platform, &[0x61FE, 0xA300, 0xF133]);
+        run(&mut emulator, 3);
+        assert_eq!(emulator.memory()[0x300..][..3], [2, 5, 4]);
+        assert_eq!(emulator.address_register(), 0x300);
+    }
+}
+
+#[test]
+fn dump_and_restore() {
+    for platform in PLATFORMS {
+        let increments = platform.quirks().memory_increments_i;
+
+        // LD V0, 1; LD V1, 2; LD V2, 3; LD I, 0x300; LD [I], V2
+        let mut emulator = load(platform, &[0x6001, 0x6102, 0x6203, 0xA300, 0xF255]);
+        run(&mut emulator, 5);
+        assert_eq!(emulator.memory()[0x300..][..4], [1, 2, 3, 0]);
+        let expected = if increments { 0x303 } else { 0x300 };
+        assert_eq!(emulator.address_register(), expected);
+
+        // LD I, 0x200; LD V1, [I]
+        let mut emulator = load(platform, &[0xA200, 0xF165]);
+        run(&mut emulator, 2);
+        assert_eq!(
+            (v(&emulator, 0), v(&emulator, 1), v(&emulator, 2)),
+            (0xA2, 0x00, 0)
+        );
+        let expected = if increments { 0x202 } else { 0x200 };
+        assert_eq!(emulator.address_register(), expected);
+    }
+}
+
+#[test]
+fn memory_access_out_of_bounds() {
+    for platform in PLATFORMS {
+        // LD I, 0xFFE; LD [I], V3
+        let mut emulator = load(platform, &[0xAFFE, 0xF355]);
+        run(&mut emulator, 1);
+        let err = emulator.step().unwrap_err();
+        assert_eq!(
+            err.fault,
+            Fault::OutOfMemory {
+                address: 0xFFE,
+                len: 4
+            }
+        );
+        assert_eq!((err.pc, err.opcode, err.cycle), (0x202, Some(0xF355), 1));
+    }
+}
+
+#[test]
+fn invalid_instruction() {
+    for platform in PLATFORMS {
+        let mut emulator = load(platform, &[0x5121]);
+        let err = emulator.step().unwrap_err();
+        assert_eq!(err.fault, Fault::InvalidInstruction);
+        assert_eq!(err.opcode, Some(0x5121));
+        assert_eq!(
+            err.to_string(),
+            "invalid instruction at PC 0x200 (opcode 5121, cycle 0)"
+        );
+    }
+}
+
+#[test]
+fn invalid_program_counter() {
+    for platform in PLATFORMS {
+        // JP 0x203
+        let mut emulator = load(platform, &[0x1203]);
+        run(&mut emulator, 1);
+
+        let err = emulator.step().unwrap_err();
+        assert_eq!(err.fault, Fault::InvalidProgramCounter);
+        assert_eq!((err.pc, err.opcode), (0x203, None));
+    }
+
+    // LD V0, 0xFE; JP V0, 0xF02
+    let mut emulator = load(Platform::Vip, &[0x60FE, 0xBF02]);
+    run(&mut emulator, 2);
+    assert_eq!(emulator.step().unwrap_err().pc, 0x1000);
+}
+
+#[test]
+fn program_too_large() {
+    let mut emulator = Emulator::new();
+    let err = emulator.load(&[0; MEMORY_SIZE]).unwrap_err();
+    assert_eq!(err.fault, Fault::ProgramTooLarge { len: MEMORY_SIZE });
+}
@@ -0,0 +1,72 @@
+/// Width of the Chip-8 display in pixels.
+pub const WIDTH: usize = 64;
+
+/// Height of the Chip-8 display in pixels.
+pub const HEIGHT: usize = 32;
+
+/// [Framebuffer] is the 64x32 monochrome display of the Chip-8 emulator. Each row is
+/// stored as a single [u64] with the left-most pixel in the most significant bit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Framebuffer([u64; HEIGHT]);
+
+impl Framebuffer {
+    /// Constructs a framebuffer from raw rows as returned by [Framebuffer::rows].
+    pub fn from_rows(rows: [u64; HEIGHT]) -> Self {
+        Framebuffer(rows)
+    }
+
+    /// Returns the raw rows of the framebuffer, one bit per pixel with the left-most
+    /// pixel in the most significant bit.
+    pub fn rows(&self) -> &[u64; HEIGHT] {
+        &self.0
+    }
+
+    /// Turns off every pixel in the framebuffer.
+    pub fn clear(&mut self) {
+        self.0 = [0; HEIGHT];
+    }
+
+    /// Returns whether the pixel at a given position is turned on.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the position lies outside of the display.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        assert!(x < WIDTH && y < HEIGHT);
+        self.0[y] & (1 << (WIDTH - 1 - x)) != 0
+    }
+
+    /// XORs a sprite onto the framebuffer with its top left corner at `(x, y)`. The
+    /// starting position always wraps around the display while the rest of the sprite
+    /// is either clipped or wrapped depending on `clip`. Returns true if any pixel was
+    /// turned off as a result.
+    pub fn draw_sprite(&mut self, x: usize, y: usize, sprite: &[u8], clip: bool) -> bool {
+        let x = x % WIDTH;
+        let y = y % HEIGHT;
+        let mut collision = false;
+
+        for (i, &byte) in sprite.iter().enumerate() {
+            let row = y + i;
+            if clip && row >= HEIGHT {
+                break;
+            }
+
+            let mut bits = (byte as u64).rotate_right(8).rotate_right(x as u32);
+            if clip && x > WIDTH - 8 {
+                bits &= u64::MAX >> (x - (WIDTH - 8));
+            }
+
+            let row = &mut self.0[row % HEIGHT];
+            collision |= *row & bits != 0;
+            *row ^= bits;
+        }
+
+        collision
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Framebuffer([0; HEIGHT])
+    }
+}
@@ -0,0 +1,328 @@
+use std::{collections::HashMap, io};
+
+use crate::{
+    framebuffer::{Framebuffer, HEIGHT, WIDTH},
+    image::Palette,
+};
+
+/// Smallest LZW code size allowed by the GIF format, enough for a two color palette.
+const MIN_CODE_SIZE: u8 = 2;
+
+/// Largest LZW code before the code table has to be reset.
+const MAX_CODE: u16 = 4095;
+
+/// Frames per second at which the emulator produces frames.
+const FRAME_RATE: u64 = 60;
+
+/// [GifRecorder] encodes the frames produced by the emulator into an animated GIF.
+/// Identical consecutive frames are merged into one longer frame.
+pub struct GifRecorder<W: io::Write> {
+    w: W,
+    scale: usize,
+    width: u16,
+    height: u16,
+    current: Option<Framebuffer>,
+    frames: u64,
+    written_centis: u64,
+}
+
+impl<W: io::Write> GifRecorder<W> {
+    /// Writes the GIF header and returns a recorder ready to accept frames. Fails if the
+    /// scaled display does not fit the 16-bit dimensions of a GIF.
+    pub fn new(mut w: W, scale: usize, palette: Palette) -> io::Result<Self> {
+        let scale = scale.max(1);
+        let dimension = |size: usize| {
+            let scaled = size.checked_mul(scale).and_then(|n| u16::try_from(n).ok());
+            scaled.ok_or_else(|| {
+                let message = format!("scale {} is too large for a GIF", scale);
+                io::Error::new(io::ErrorKind::InvalidInput, message)
+            })
+        };
+        let (width, height) = (dimension(WIDTH)?, dimension(HEIGHT)?);
+
+        w.write_all(b"GIF89a")?;
+        w.write_all(&width.to_le_bytes())?;
+        w.write_all(&height.to_le_bytes())?;
+        // Global color table with 2 entries, background color 0, square pixels.
+        w.write_all(&[0x80, 0, 0])?;
+        w.write_all(&palette.background.0)?;
+        w.write_all(&palette.foreground.0)?;
+
+        // Loop the animation forever.
+        w.write_all(&[0x21, 0xFF, 0x0B])?;
+        w.write_all(b"NETSCAPE2.0")?;
+        w.write_all(&[0x03, 0x01, 0x00, 0x00, 0x00])?;
+
+        Ok(GifRecorder {
+            w,
+            scale,
+            width,
+            height,
+            current: None,
+            frames: 0,
+            written_centis: 0,
+        })
+    }
+
+    /// Adds the next 60 Hz frame to the animation.
+    pub fn frame(&mut self, framebuffer: &Framebuffer) -> io::Result<()> {
+        if self.current.as_ref() != Some(framebuffer) {
+            self.flush()?;
+            self.current = Some(*framebuffer);
+        }
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Writes any pending frame followed by the GIF trailer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush()?;
+        self.w.write_all(&[0x3B])?;
+        Ok(self.w)
+    }
+
+    /// Writes the frame currently being held, if any, with a delay covering every 60 Hz
+    /// frame it was shown for.
+    fn flush(&mut self) -> io::Result<()> {
+        let framebuffer = match self.current.take() {
+            Some(framebuffer) => framebuffer,
+            None => return Ok(()),
+        };
+
+        // GIF delays are in hundredths of a second; round against the total elapsed
+        // time so that errors do not accumulate over long recordings.
+        let end_centis = (self.frames * 100 + FRAME_RATE / 2) / FRAME_RATE;
+        let delay = (end_centis - self.written_centis).min(u16::MAX as u64) as u16;
+        self.written_centis += delay as u64;
+
+        let (width, height) = (self.width, self.height);
+
+        // Graphic control extension carrying the frame delay.
+        self.w.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+        self.w.write_all(&delay.to_le_bytes())?;
+        self.w.write_all(&[0x00, 0x00])?;
+
+        // Image descriptor covering the whole screen without a local color table.
+        self.w.write_all(&[0x2C, 0, 0, 0, 0])?;
+        self.w.write_all(&width.to_le_bytes())?;
+        self.w.write_all(&height.to_le_bytes())?;
+        self.w.write_all(&[0x00])?;
+
+        let mut indices = Vec::with_capacity(width as usize * height as usize);
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                indices.push(framebuffer.pixel(x / self.scale, y / self.scale) as u8);
+            }
+        }
+
+        self.w.write_all(&[MIN_CODE_SIZE])?;
+        for block in lzw_encode(&indices).chunks(255) {
+            self.w.write_all(&[block.len() as u8])?;
+            self.w.write_all(block)?;
+        }
+
+        self.w.write_all(&[0x00])
+    }
+}
+
+/// Compresses palette indices using the variable code size LZW scheme used by GIF.
+fn lzw_encode(indices: &[u8]) -> Vec<u8> {
+    let clear = 1u16 << MIN_CODE_SIZE;
+    let end = clear + 1;
+
+    let mut out = BitWriter::default();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut code_size = MIN_CODE_SIZE + 1;
+    let mut next_code = end + 1;
+
+    out.write(clear, code_size);
+    let mut prefix = match indices.first() {
+        Some(&first) => first as u16,
+        None => {
+            out.write(end, code_size);
+            return out.finish();
+        }
+    };
+
+    for &index in &indices[1..] {
+        if let Some(&code) = table.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+
+        out.write(prefix, code_size);
+        if next_code > MAX_CODE {
+            out.write(clear, code_size);
+            table.clear();
+            code_size = MIN_CODE_SIZE + 1;
+            next_code = end + 1;
+        } else {
+            table.insert((prefix, index), next_code);
+            if next_code == 1 << code_size {
+                code_size += 1;
+            }
+
+            next_code += 1;
+        }
+
+        prefix = index as u16;
+    }
+
+    out.write(prefix, code_size);
+    out.write(end, code_size);
+    out.finish()
+}
+
+/// [BitWriter] packs variable width codes into bytes, least significant bit first.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Reference decoder for the codes produced by [lzw_encode].
+    fn lzw_decode(data: &[u8]) -> Vec<u8> {
+        let clear = 1u16 << MIN_CODE_SIZE;
+        let end = clear + 1;
+        let mut bits = data.iter().flat_map(|b| (0..8).map(move |i| (b >> i) & 1));
+        let mut read = |size: u8| -> u16 {
+            (0..size).fold(0, |code, i| code | ((bits.next().unwrap() as u16) << i))
+        };
+
+        let mut out = Vec::new();
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut code_size = MIN_CODE_SIZE + 1;
+        let mut previous: Option<Vec<u8>> = None;
+        loop {
+            let code = read(code_size);
+            if code == clear {
+                table = (0..clear).map(|i| vec![i as u8]).collect();
+                table.extend([vec![], vec![]]);
+                code_size = MIN_CODE_SIZE + 1;
+                previous = None;
+                continue;
+            }
+
+            if code == end {
+                return out;
+            }
+
+            let entry = match (table.get(code as usize), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(prev)) => [prev.clone(), vec![prev[0]]].concat(),
+                (None, None) => panic!("invalid code"),
+            };
+
+            if let Some(prev) = previous {
+                table.push([prev, vec![entry[0]]].concat());
+                if table.len() == 1 << code_size && code_size < 12 {
+                    code_size += 1;
+                }
+            }
+
+            out.extend_from_slice(&entry);
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trip() {
+        let mut state = 12345u32;
+        let noise: Vec<u8> = (0..40_000)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                ((state >> 16) & 1) as u8
+            })
+            .collect();
+
+        for data in [vec![], vec![1], vec![0; 10_000], noise] {
+            assert_eq!(lzw_decode(&lzw_encode(&data)), data);
+        }
+    }
+
+    #[test]
+    fn lzw_matches_reference() {
+        // The 10x10 sample image from "What's in a GIF", along with the image data of
+        // the reference file.
+        let rows = [
+            "1111122222",
+            "1111122222",
+            "1111122222",
+            "1110000222",
+            "1110000222",
+            "2220000111",
+            "2220000111",
+            "2222211111",
+            "2222211111",
+            "2222211111",
+        ];
+        let indices: Vec<u8> = rows.concat().bytes().map(|b| b - b'0').collect();
+        let expected = [
+            0x8C, 0x2D, 0x99, 0x87, 0x2A, 0x1C, 0xDC, 0x33, 0xA0, 0x02, 0x75, 0xEC, 0x95, 0xFA,
+            0xA8, 0xDE, 0x60, 0x8C, 0x04, 0x91, 0x4C, 0x01,
+        ];
+        assert_eq!(lzw_encode(&indices), expected);
+    }
+
+    #[test]
+    fn identical_frames_are_merged() {
+        let mut recorder = GifRecorder::new(Vec::new(), 1, Palette::default()).unwrap();
+        let mut framebuffer = Framebuffer::default();
+        for _ in 0..30 {
+            recorder.frame(&framebuffer).unwrap();
+        }
+
+        framebuffer.draw_sprite(0, 0, &[0xFF], false);
+        for _ in 0..30 {
+            recorder.frame(&framebuffer).unwrap();
+        }
+
+        let data = recorder.finish().unwrap();
+        let extensions: Vec<_> = data
+            .windows(8)
+            .filter(|w| w[..4] == [0x21, 0xF9, 0x04, 0x00])
+            .map(|w| u16::from_le_bytes([w[4], w[5]]))
+            .collect();
+
+        assert_eq!(extensions, vec![50, 50]);
+        assert_eq!(data.last(), Some(&0x3B));
+    }
+
+    #[test]
+    fn scale_limit() {
+        let data = GifRecorder::new(Vec::new(), 1023, Palette::default())
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert_eq!(data[6..10], [0xC0, 0xFF, 0xE0, 0x7F]);
+
+        let err = GifRecorder::new(Vec::new(), 1024, Palette::default()).err();
+        assert_eq!(err.map(|err| err.kind()), Some(io::ErrorKind::InvalidInput));
+    }
+}
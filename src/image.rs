@@ -0,0 +1,684 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    io,
+    path::Path,
+    str::FromStr,
+};
+
+use crate::{
+    checksum::crc32,
+    framebuffer::{Framebuffer, HEIGHT, WIDTH},
+};
+
+/// Signature at the start of every PNG file.
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+/// Largest amount of data which fits into a single stored deflate block.
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+/// Color of pixels which are lit in the expected image but not the actual one.
+const DIFF_MISSING: Color = Color([0xFF, 0x00, 0x00]);
+
+/// Color of pixels which are lit in the actual image but not the expected one.
+const DIFF_EXTRA: Color = Color([0x00, 0xFF, 0x00]);
+
+/// [Color] is a 24-bit RGB color.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Color(pub [u8; 3]);
+
+impl FromStr for Color {
+    type Err = String;
+
+    /// Parses a color written as six hexadecimal digits with an optional leading `#`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s.strip_prefix('#').unwrap_or(s);
+        if digits.len() != 6 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!("invalid color '{}', expected RRGGBB", s));
+        }
+
+        let value = u32::from_str_radix(digits, 16).unwrap();
+
+        let [_, r, g, b] = value.to_be_bytes();
+        Ok(Color([r, g, b]))
+    }
+}
+
+impl Display for Color {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let [r, g, b] = self.0;
+        write!(f, "#{:02x}{:02x}{:02x}", r, g, b)
+    }
+}
+
+/// [Palette] holds the colors used for lit and unlit pixels when exporting images.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub foreground: Color,
+    pub background: Color,
+}
+
+impl Palette {
+    /// Returns whether a pixel of the given color shows a lit pixel, being closer to the
+    /// foreground color than to the background color.
+    pub fn is_lit(&self, color: [u8; 3]) -> bool {
+        let distance = |to: Color| -> u32 {
+            let channels = color.iter().zip(to.0);
+            channels.map(|(&a, b)| (a.abs_diff(b) as u32).pow(2)).sum()
+        };
+
+        distance(self.foreground) < distance(self.background)
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette {
+            foreground: Color([0xFF, 0xFF, 0xFF]),
+            background: Color([0x00, 0x00, 0x00]),
+        }
+    }
+}
+
+/// [ImageFormat] enumerates the supported image file formats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+}
+
+impl ImageFormat {
+    /// Picks an image format based on the extension of a file name.
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            _ => None,
+        }
+    }
+}
+
+/// [Screenshot] exports the contents of a [Framebuffer] as an image with each pixel
+/// scaled up to a `scale` by `scale` square.
+pub struct Screenshot {
+    scale: usize,
+    palette: Palette,
+}
+
+impl Screenshot {
+    /// Constructs a screenshot exporter with a scale of 1 and the default palette.
+    pub fn new() -> Self {
+        Screenshot {
+            scale: 1,
+            palette: Palette::default(),
+        }
+    }
+
+    /// Sets the size of each Chip-8 pixel in the exported image.
+    pub fn with_scale(self, scale: usize) -> Self {
+        Screenshot {
+            scale: scale.max(1),
+            palette: self.palette,
+        }
+    }
+
+    /// Sets the colors used for lit and unlit pixels.
+    pub fn with_palette(self, palette: Palette) -> Self {
+        Screenshot {
+            scale: self.scale,
+            palette,
+        }
+    }
+
+    /// Returns the colors used for lit and unlit pixels.
+    pub fn palette(&self) -> Palette {
+        self.palette
+    }
+
+    /// Returns the width and height of exported images in pixels.
+    pub fn dimensions(&self) -> (usize, usize) {
+        (WIDTH * self.scale, HEIGHT * self.scale)
+    }
+
+    /// Writes a framebuffer as an image in a given format.
+    pub fn write<W: io::Write>(
+        &self,
+        framebuffer: &Framebuffer,
+        format: ImageFormat,
+        w: &mut W,
+    ) -> io::Result<()> {
+        match format {
+            ImageFormat::Ppm => self.write_ppm(framebuffer, w),
+            ImageFormat::Png => self.write_png(framebuffer, w),
+        }
+    }
+
+    /// Writes a framebuffer as a binary PPM (P6) image.
+    pub fn write_ppm<W: io::Write>(&self, framebuffer: &Framebuffer, w: &mut W) -> io::Result<()> {
+        let (width, height) = self.dimensions();
+        write!(w, "P6\n{} {}\n255\n", width, height)?;
+
+        let mut row = Vec::with_capacity(width * 3);
+        for y in 0..height {
+            row.clear();
+            for x in 0..width {
+                let lit = framebuffer.pixel(x / self.scale, y / self.scale);
+                row.extend_from_slice(&self.color(lit).0);
+            }
+
+            w.write_all(&row)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a framebuffer as a PNG image. The image is stored as a 1-bit indexed
+    /// image with a two color palette which keeps files small without needing a real
+    /// compressor; the pixel data is wrapped in uncompressed deflate blocks.
+    pub fn write_png<W: io::Write>(&self, framebuffer: &Framebuffer, w: &mut W) -> io::Result<()> {
+        let palette = [self.palette.background, self.palette.foreground];
+        self.write_indexed_png(w, &palette, |x, y| framebuffer.pixel(x, y) as u8)
+    }
+
+    /// Writes a PNG image highlighting the differences between two framebuffers. Pixels
+    /// lit in both are drawn in the foreground color, pixels only lit in `expected` in
+    /// red and pixels only lit in `actual` in green.
+    pub fn write_diff_png<W: io::Write>(
+        &self,
+        expected: &Framebuffer,
+        actual: &Framebuffer,
+        w: &mut W,
+    ) -> io::Result<()> {
+        let palette = [
+            self.palette.background,
+            DIFF_MISSING,
+            DIFF_EXTRA,
+            self.palette.foreground,
+        ];
+
+        self.write_indexed_png(w, &palette, |x, y| {
+            expected.pixel(x, y) as u8 | (actual.pixel(x, y) as u8) << 1
+        })
+    }
+
+    /// Writes an indexed PNG image of up to four colors with the palette index of each
+    /// Chip-8 pixel given by `index`.
+    fn write_indexed_png<W, F>(&self, w: &mut W, palette: &[Color], index: F) -> io::Result<()>
+    where
+        W: io::Write,
+        F: Fn(usize, usize) -> u8,
+    {
+        let (width, height) = self.dimensions();
+        write_indexed_png(w, width, height, palette, |x, y| {
+            index(x / self.scale, y / self.scale)
+        })
+    }
+
+    fn color(&self, lit: bool) -> Color {
+        if lit {
+            self.palette.foreground
+        } else {
+            self.palette.background
+        }
+    }
+}
+
+impl Default for Screenshot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads a binary PPM (P6) image such as one written by [Screenshot::write_ppm] back
+/// into a framebuffer. The image may be any whole multiple of the display size and a
+/// pixel counts as lit when its color is closer to the foreground of `palette` than to
+/// the background.
+pub fn read_ppm<R: io::Read>(r: &mut R, palette: &Palette) -> io::Result<Framebuffer> {
+    let mut data = Vec::new();
+    r.read_to_end(&mut data)?;
+
+    let mut header = Vec::new();
+    let mut pos = 0;
+    while header.len() < 4 {
+        match data.get(pos) {
+            Some(b'#') => {
+                while data.get(pos).is_some_and(|&b| b != b'\n') {
+                    pos += 1;
+                }
+            }
+
+            Some(b) if b.is_ascii_whitespace() => pos += 1,
+            Some(_) => {
+                let start = pos;
+                while data.get(pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+                    pos += 1;
+                }
+
+                header.push(String::from_utf8_lossy(&data[start..pos]).into_owned());
+            }
+
+            None => return Err(invalid("truncated PPM header")),
+        }
+    }
+
+    // A single whitespace byte separates the header from the pixel data.
+    pos += 1;
+    let number = |s: &str| {
+        s.parse::<usize>()
+            .map_err(|_| invalid("invalid PPM header"))
+    };
+    let (width, height) = (number(&header[1])?, number(&header[2])?);
+    if header[0] != "P6" || number(&header[3])? != 255 {
+        return Err(invalid("only 8-bit binary PPM images are supported"));
+    }
+
+    let pixels = &data[pos.min(data.len())..];
+    if pixels.len() < width * height * 3 {
+        return Err(invalid("truncated PPM pixel data"));
+    }
+
+    sample(width, height, palette, |x, y| {
+        let offset = (y * width + x) * 3;
+        [pixels[offset], pixels[offset + 1], pixels[offset + 2]]
+    })
+}
+
+/// Reads a PNG image such as one written by [Screenshot::write_png] back into a
+/// framebuffer, classifying pixels like [read_ppm]. Only indexed images with unfiltered
+/// scanlines stored in uncompressed deflate blocks, as written by this crate, are
+/// supported.
+pub fn read_png<R: io::Read>(r: &mut R, palette: &Palette) -> io::Result<Framebuffer> {
+    let mut data = Vec::new();
+    r.read_to_end(&mut data)?;
+
+    let (mut header, mut colors, mut compressed) = (None, None, Vec::new());
+    for (kind, chunk) in png_chunks(&data)? {
+        match &kind {
+            b"IHDR" => header = Some(chunk),
+            b"PLTE" => colors = Some(chunk),
+            b"IDAT" => compressed.extend_from_slice(chunk),
+            _ => {}
+        }
+    }
+
+    let header = header.filter(|h| h.len() == 13);
+    let header = header.ok_or_else(|| invalid("missing PNG header"))?;
+    let colors = colors.ok_or_else(|| invalid("missing PNG palette"))?;
+    let width = u32::from_be_bytes(header[0..4].try_into().unwrap()) as usize;
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap()) as usize;
+    let depth = header[8] as usize;
+    if header[9..] != [3, 0, 0, 0] || !matches!(depth, 1 | 2 | 4 | 8) {
+        return Err(invalid(
+            "only indexed, non-interlaced PNG images are supported",
+        ));
+    }
+
+    let pixels = inflate_stored(compressed)?;
+    let stride = 1 + (width * depth).div_ceil(8);
+    if pixels.len() < stride * height {
+        return Err(invalid("truncated PNG pixel data"));
+    }
+
+    if pixels.chunks(stride).take(height).any(|line| line[0] != 0) {
+        return Err(invalid("filtered PNG scanlines are not supported"));
+    }
+
+    let per_byte = 8 / depth;
+    let index = |x: usize, y: usize| {
+        let byte = pixels[y * stride + 1 + x / per_byte];
+        let shift = 8 - depth * (x % per_byte + 1);
+        (byte >> shift) as usize & ((1 << depth) - 1)
+    };
+
+    if (0..height).any(|y| (0..width).any(|x| 3 * index(x, y) + 3 > colors.len())) {
+        return Err(invalid("PNG pixel outside of the palette"));
+    }
+
+    sample(width, height, palette, |x, y| {
+        let i = 3 * index(x, y);
+        [colors[i], colors[i + 1], colors[i + 2]]
+    })
+}
+
+/// Reads an image in a given format back into a framebuffer with [read_ppm] or
+/// [read_png].
+pub fn read_image<R: io::Read>(
+    r: &mut R,
+    format: ImageFormat,
+    palette: &Palette,
+) -> io::Result<Framebuffer> {
+    match format {
+        ImageFormat::Ppm => read_ppm(r, palette),
+        ImageFormat::Png => read_png(r, palette),
+    }
+}
+
+/// Builds a framebuffer from an image of a whole multiple of the display size, taking
+/// the top left image pixel of each Chip-8 pixel with `color`.
+fn sample<F>(width: usize, height: usize, palette: &Palette, color: F) -> io::Result<Framebuffer>
+where
+    F: Fn(usize, usize) -> [u8; 3],
+{
+    let scale = width / WIDTH;
+    if scale == 0 || width != WIDTH * scale || height != HEIGHT * scale {
+        return Err(invalid("image size is not a multiple of the display size"));
+    }
+
+    let mut rows = [0u64; HEIGHT];
+    for (y, row) in rows.iter_mut().enumerate() {
+        for x in 0..WIDTH {
+            if palette.is_lit(color(x * scale, y * scale)) {
+                *row |= 1 << (WIDTH - 1 - x);
+            }
+        }
+    }
+
+    Ok(Framebuffer::from_rows(rows))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Writes an indexed PNG image of up to four colors with the palette index of each image
+/// pixel given by `index`. The image is stored with the smallest bit depth the palette
+/// allows and uncompressed deflate blocks.
+pub(crate) fn write_indexed_png<W, F>(
+    w: &mut W,
+    width: usize,
+    height: usize,
+    palette: &[Color],
+    index: F,
+) -> io::Result<()>
+where
+    W: io::Write,
+    F: Fn(usize, usize) -> u8,
+{
+    let depth = if palette.len() <= 2 { 1 } else { 2 };
+    let per_byte = 8 / depth;
+    let stride = width.div_ceil(per_byte);
+
+    let mut pixels = Vec::with_capacity((stride + 1) * height);
+    for y in 0..height {
+        // Each scanline starts with its filter type, 0 meaning unfiltered.
+        pixels.push(0);
+        let mut byte = 0u8;
+        for x in 0..width {
+            let shift = 8 - depth * (x % per_byte + 1);
+            byte |= index(x, y) << shift;
+            if x % per_byte == per_byte - 1 || x == width - 1 {
+                pixels.push(byte);
+                byte = 0;
+            }
+        }
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // Color type 3 (indexed), default compression, filter and interlace.
+    header.extend_from_slice(&[depth as u8, 3, 0, 0, 0]);
+
+    let palette: Vec<u8> = palette.iter().flat_map(|color| color.0).collect();
+
+    w.write_all(&PNG_SIGNATURE)?;
+    write_png_chunk(w, b"IHDR", &header)?;
+    write_png_chunk(w, b"PLTE", &palette)?;
+    write_png_chunk(w, b"IDAT", &zlib_stored(&pixels))?;
+    write_png_chunk(w, b"IEND", &[])
+}
+
+fn write_png_chunk<W: io::Write>(w: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let mut checked = Vec::with_capacity(4 + data.len());
+    checked.extend_from_slice(kind);
+    checked.extend_from_slice(data);
+
+    w.write_all(&(data.len() as u32).to_be_bytes())?;
+    w.write_all(&checked)?;
+    w.write_all(&crc32(&checked).to_be_bytes())
+}
+
+/// Wraps data in a zlib stream made up of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let blocks = data.len() / MAX_STORED_BLOCK + 1;
+    let mut out = Vec::with_capacity(2 + data.len() + blocks * 5 + 4);
+    out.extend_from_slice(&[0x78, 0x01]);
+
+    let mut chunks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if chunks.peek().is_none() {
+        out.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+
+    while let Some(chunk) = chunks.next() {
+        let last = chunks.peek().is_none();
+        let len = chunk.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(chunk);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+/// Splits a PNG file into its chunks up to `IEND`, checking the signature and the CRC of
+/// each chunk.
+fn png_chunks(data: &[u8]) -> io::Result<Vec<([u8; 4], &[u8])>> {
+    if !data.starts_with(&PNG_SIGNATURE) {
+        return Err(invalid("not a PNG image"));
+    }
+
+    let mut chunks = Vec::new();
+    let mut rest = &data[PNG_SIGNATURE.len()..];
+    while rest.len() >= 12 {
+        let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        if rest.len() - 12 < len {
+            break;
+        }
+
+        let (checked, crc) = (&rest[4..8 + len], &rest[8 + len..12 + len]);
+        if crc32(checked).to_be_bytes() != crc {
+            return Err(invalid("PNG chunk CRC mismatch"));
+        }
+
+        let kind = checked[..4].try_into().unwrap();
+        chunks.push((kind, &checked[4..]));
+        if &kind == b"IEND" {
+            return Ok(chunks);
+        }
+
+        rest = &rest[12 + len..];
+    }
+
+    Err(invalid("truncated PNG image"))
+}
+
+/// Inflates a zlib stream made up of uncompressed deflate blocks, as written by
+/// [zlib_stored], checking its checksum.
+fn inflate_stored(data: Vec<u8>) -> io::Result<Vec<u8>> {
+    let truncated = || invalid("truncated PNG pixel data");
+    if data.len() < 2
+        || data[0] & 0x0F != 8
+        || !u16::from_be_bytes([data[0], data[1]]).is_multiple_of(31)
+    {
+        return Err(invalid("invalid zlib header in PNG pixel data"));
+    }
+
+    let mut out = Vec::new();
+    let mut pos = 2;
+    loop {
+        let block = data.get(pos..pos + 5).ok_or_else(truncated)?;
+        if block[0] >> 1 != 0 {
+            return Err(invalid("compressed PNG pixel data is not supported"));
+        }
+
+        let len = u16::from_le_bytes([block[1], block[2]]);
+        if u16::from_le_bytes([block[3], block[4]]) != !len {
+            return Err(invalid("corrupt deflate block in PNG pixel data"));
+        }
+
+        pos += 5;
+        let stored = data.get(pos..pos + len as usize).ok_or_else(truncated)?;
+        out.extend_from_slice(stored);
+        pos += len as usize;
+        if block[0] & 1 == 1 {
+            break;
+        }
+    }
+
+    if data.get(pos..pos + 4) != Some(&adler32(&out).to_be_bytes()[..]) {
+        return Err(invalid("PNG pixel data checksum mismatch"));
+    }
+
+    Ok(out)
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn adler32_known_value() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn parse_color() {
+        assert_eq!("#1a2B3c".parse(), Ok(Color([0x1A, 0x2B, 0x3C])));
+        assert_eq!("102030".parse(), Ok(Color([0x10, 0x20, 0x30])));
+        assert_eq!(Color([0x1A, 0x2B, 0x3C]).to_string(), "#1a2b3c");
+        assert!("12345".parse::<Color>().is_err());
+        assert!("+FFFFF".parse::<Color>().is_err());
+        assert!("+FFFFFF".parse::<Color>().is_err());
+        assert!("#-12345".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn png_decodes() {
+        let mut framebuffer = Framebuffer::default();
+        framebuffer.draw_sprite(4, 1, &[0xF0, 0x90], false);
+
+        let palette = Palette {
+            foreground: Color([0x11, 0x22, 0x33]),
+            background: Color([0x44, 0x55, 0x66]),
+        };
+        let mut data = Vec::new();
+        Screenshot::new()
+            .with_palette(palette)
+            .write_png(&framebuffer, &mut data)
+            .unwrap();
+
+        // IEND has no data so its CRC is the same in every PNG file.
+        assert!(data.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]));
+
+        let chunks = png_chunks(&data).unwrap();
+        let kinds: Vec<&[u8]> = chunks.iter().map(|(kind, _)| &kind[..]).collect();
+        assert_eq!(kinds, [b"IHDR", b"PLTE", b"IDAT", b"IEND"]);
+        assert_eq!(chunks[0].1, [0, 0, 0, 64, 0, 0, 0, 32, 1, 3, 0, 0, 0]);
+        assert_eq!(chunks[1].1, [0x44, 0x55, 0x66, 0x11, 0x22, 0x33]);
+
+        let pixels = inflate_stored(chunks[2].1.to_vec()).unwrap();
+        assert_eq!(pixels.len(), HEIGHT * (1 + WIDTH / 8));
+        for (y, row) in pixels.chunks(1 + WIDTH / 8).enumerate() {
+            assert_eq!(row[0], 0, "scanline {} is filtered", y);
+            for x in 0..WIDTH {
+                let lit = row[1 + x / 8] & (0x80 >> (x % 8)) != 0;
+                assert_eq!(lit, framebuffer.pixel(x, y), "pixel {},{}", x, y);
+            }
+        }
+        assert_eq!(pixels[9..11], [0, 0x0F]);
+        assert_eq!(pixels[18..20], [0, 0x09]);
+
+        assert_eq!(
+            read_png(&mut data.as_slice(), &palette).unwrap(),
+            framebuffer
+        );
+    }
+
+    #[test]
+    fn png_spans_deflate_blocks() {
+        let mut data = Vec::new();
+        Screenshot::new()
+            .with_scale(20)
+            .write_png(&Framebuffer::default(), &mut data)
+            .unwrap();
+
+        let chunks = png_chunks(&data).unwrap();
+        assert_eq!(chunks[0].1[..8], [0, 0, 5, 0, 0, 0, 2, 128]);
+        let pixels = inflate_stored(chunks[2].1.to_vec()).unwrap();
+        assert_eq!(pixels.len(), 640 * (1 + 1280 / 8));
+
+        let palette = Palette::default();
+        let read = read_png(&mut data.as_slice(), &palette).unwrap();
+        assert_eq!(read, Framebuffer::default());
+
+        // Corrupting a single pixel byte breaks the chunk CRC.
+        let last = data.len() - 20;
+        data[last] ^= 1;
+        assert!(read_png(&mut data.as_slice(), &palette).is_err());
+    }
+
+    #[test]
+    fn ppm_dimensions() {
+        let mut data = Vec::new();
+        Screenshot::new()
+            .with_scale(3)
+            .write_ppm(&Framebuffer::default(), &mut data)
+            .unwrap();
+
+        assert!(data.starts_with(b"P6\n192 96\n255\n"));
+        assert_eq!(data.len(), 14 + 192 * 96 * 3);
+    }
+
+    #[test]
+    fn ppm_round_trip() {
+        let mut framebuffer = Framebuffer::default();
+        framebuffer.draw_sprite(62, 30, &[0xF0, 0x90], false);
+
+        let mut data = Vec::new();
+        Screenshot::new()
+            .with_scale(2)
+            .write_ppm(&framebuffer, &mut data)
+            .unwrap();
+
+        let palette = Palette::default();
+        assert_eq!(
+            read_ppm(&mut data.as_slice(), &palette).unwrap(),
+            framebuffer
+        );
+    }
+
+    #[test]
+    fn ppm_dark_on_light() {
+        let mut framebuffer = Framebuffer::default();
+        framebuffer.draw_sprite(0, 0, &[0xAA], false);
+
+        let palette = Palette {
+            foreground: Color([0x00, 0x00, 0x00]),
+            background: Color([0xFF, 0xFF, 0xFF]),
+        };
+        let mut data = Vec::new();
+        Screenshot::new()
+            .with_palette(palette)
+            .write_ppm(&framebuffer, &mut data)
+            .unwrap();
+
+        assert_eq!(
+            read_ppm(&mut data.as_slice(), &palette).unwrap(),
+            framebuffer
+        );
+        assert!(palette.is_lit([0x20, 0x30, 0x10]));
+        assert!(!palette.is_lit([0xC0, 0xD0, 0xE0]));
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet},
+    io,
+};
+
+use crate::{
+    checksum::crc32,
+    data::Addr,
+    database::sha1_hex,
+    emulation::START_ADDRESS,
+    lint::Linter,
+    opcode::{Flow, Opcode},
+    quirks::Platform,
+};
+
+/// Shortest run of printable characters reported as embedded text.
+const MIN_TEXT_LEN: usize = 6;
+
+/// [Summary] describes a program without running it, to help triage unknown ROMs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Summary {
+    pub size: usize,
+    pub crc32: u32,
+    pub sha1: String,
+
+    /// Platform whose instructions the program uses, along with the address of the
+    /// first reachable instruction which needs it for anything but plain Chip-8.
+    pub platform: (Platform, Option<Addr>),
+
+    /// Where execution ends up after following the jumps at the start address.
+    pub entry_point: Addr,
+
+    /// Number of occurrences of each [Opcode] variant among the words of the program.
+    pub histogram: BTreeMap<&'static str, usize>,
+
+    /// Number of words which are not Chip-8 instructions.
+    pub undecodable: usize,
+
+    /// Bytes reachable as instructions from the start address; the rest is likely data.
+    pub code_bytes: usize,
+
+    /// Runs of printable ASCII characters by address.
+    pub text: Vec<(Addr, String)>,
+}
+
+impl Summary {
+    /// Summarises a program.
+    pub fn new(program: &[u8]) -> Self {
+        let reachability = Linter::new().reachability(program);
+        let words = program
+            .chunks_exact(2)
+            .map(|w| u16::from_be_bytes([w[0], w[1]]));
+
+        let mut histogram = BTreeMap::new();
+        let mut undecodable = 0;
+        for word in words {
+            match Opcode::decode(&word.to_be_bytes()) {
+                Some(opcode) => *histogram.entry(opcode.name()).or_insert(0) += 1,
+                None => undecodable += 1,
+            }
+        }
+
+        let word_at = |address: Addr| {
+            let offset = (address - START_ADDRESS) as usize;
+            u16::from_be_bytes([program[offset], program[offset + 1]])
+        };
+
+        let platform = reachability
+            .instructions
+            .iter()
+            .chain(&reachability.undecodable)
+            .filter_map(|&address| extension(word_at(address)).map(|p| (p, address)))
+            .max_by_key(|&(platform, address)| (platform == Platform::XoChip, !address))
+            .map_or((Platform::Vip, None), |(platform, address)| {
+                (platform, Some(address))
+            });
+
+        // Follow the chain of jumps from the start, stopping at the first address seen
+        // twice in case the jumps form a loop.
+        let mut entry_point = START_ADDRESS;
+        let mut visited = BTreeSet::new();
+        while reachability.instructions.contains(&entry_point) && visited.insert(entry_point) {
+            match Opcode::decode(&word_at(entry_point).to_be_bytes()).map(|o| o.flow()) {
+                Some(Flow::Jump(target)) => entry_point = target,
+                _ => break,
+            }
+        }
+
+        Summary {
+            size: program.len(),
+            crc32: crc32(program),
+            sha1: sha1_hex(program),
+            platform,
+            entry_point,
+            histogram,
+            undecodable,
+            code_bytes: reachability.instructions.len() * 2,
+            text: text(program),
+        }
+    }
+
+    /// Writes the summary as text, with the instruction histogram most frequent first.
+    pub fn write_report<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "size: {} bytes", self.size)?;
+        writeln!(w, "crc32: {:08x}", self.crc32)?;
+        writeln!(w, "sha1: {}", self.sha1)?;
+
+        match self.platform {
+            (platform, Some(address)) => writeln!(
+                w,
+                "detected platform: {} (from 0x{:03X})",
+                platform, address
+            )?,
+            (platform, None) => writeln!(w, "detected platform: {}", platform)?,
+        }
+
+        writeln!(w, "entry point: 0x{:03X}", self.entry_point)?;
+        writeln!(
+            w,
+            "code: {} bytes, data: {} bytes",
+            self.code_bytes,
+            self.size - self.code_bytes
+        )?;
+        writeln!(w, "undecodable words: {}", self.undecodable)?;
+
+        let mut histogram: Vec<_> = self.histogram.iter().collect();
+        histogram.sort_by_key(|&(_, count)| Reverse(count));
+        writeln!(w, "instructions:")?;
+        for (name, count) in histogram {
+            writeln!(w, "  {:<8} {}", name, count)?;
+        }
+
+        for (address, text) in &self.text {
+            writeln!(w, "text at 0x{:03X}: {:?}", address, text)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the extension which introduced an instruction word, if it is not part of
+/// plain Chip-8.
+pub fn extension(word: u16) -> Option<Platform> {
+    match word {
+        0x00C1..=0x00CF | 0x00FB..=0x00FF => Some(Platform::Schip),
+        0x00D0..=0x00DF | 0xF000 | 0xF002 => Some(Platform::XoChip),
+        _ => match (word >> 12, word & 0x000F, word & 0x00FF) {
+            (0xD, 0x0, _) | (0xF, _, 0x30 | 0x75 | 0x85) => Some(Platform::Schip),
+            (0x5, 0x2 | 0x3, _) | (0xF, _, 0x01 | 0x3A) => Some(Platform::XoChip),
+            _ => None,
+        },
+    }
+}
+
+/// Finds runs of printable ASCII characters, which are often titles or credits.
+fn text(program: &[u8]) -> Vec<(Addr, String)> {
+    let mut text = Vec::new();
+    let mut start = 0;
+    for (i, &byte) in program.iter().chain(&[0]).enumerate() {
+        if (0x20..0x7F).contains(&byte) {
+            continue;
+        }
+
+        if i - start >= MIN_TEXT_LEN {
+            let run = String::from_utf8_lossy(&program[start..i]).into_owned();
+            text.push((START_ADDRESS + start as Addr, run));
+        }
+
+        start = i + 1;
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn extensions() {
+        assert_eq!(extension(0x00E0), None);
+        assert_eq!(extension(0xD125), None);
+        assert_eq!(extension(0x00FF), Some(Platform::Schip));
+        assert_eq!(extension(0x00C4), Some(Platform::Schip));
+        assert_eq!(extension(0xD120), Some(Platform::Schip));
+        assert_eq!(extension(0xF375), Some(Platform::Schip));
+        assert_eq!(extension(0x5122), Some(Platform::XoChip));
+        assert_eq!(extension(0x5120), None);
+        assert_eq!(extension(0xF000), Some(Platform::XoChip));
+        assert_eq!(extension(0xF201), Some(Platform::XoChip));
+        assert_eq!(extension(0x00D2), Some(Platform::XoChip));
+    }
+
+    #[test]
+    fn summary() {
+        // JP 0x20C; "HELLO!"; SYS 0x000; (invalid); LD V0, 0x01; HIGH; JP 0x210
+        let mut program = vec![0x12, 0x0C];
+        program.extend_from_slice(b"HELLO!");
+        program.extend_from_slice(&[0x00, 0x00, 0x50, 0x01]);
+        program.extend_from_slice(&[0x60, 0x01, 0x00, 0xFF, 0x12, 0x10]);
+
+        let summary = Summary::new(&program);
+        assert_eq!(summary.size, 18);
+        assert_eq!(summary.entry_point, 0x20C);
+        assert_eq!(summary.platform, (Platform::Schip, Some(0x20E)));
+        assert_eq!(summary.code_bytes, 8);
+        assert_eq!(summary.text, [(0x202, String::from("HELLO!"))]);
+        assert_eq!(summary.histogram["Jp"], 2);
+        assert_eq!(summary.undecodable, 1);
+
+        let mut out = Vec::new();
+        summary.write_report(&mut out).unwrap();
+        let report = String::from_utf8(out).unwrap();
+        assert!(report.contains("detected platform: schip (from 0x20E)\nentry point: 0x20C\n"));
+        assert!(report.contains("instructions:\n  Sne      3\n  Jp       2\n"));
+        assert!(report.ends_with("text at 0x202: \"HELLO!\"\n"));
+    }
+
+    #[test]
+    fn entry_point_loop() {
+        // JP 0x204; JP 0x204; JP 0x202
+        let summary = Summary::new(&[0x12, 0x04, 0x12, 0x04, 0x12, 0x02]);
+        assert_eq!(summary.entry_point, 0x204);
+
+        // JP 0x200
+        assert_eq!(Summary::new(&[0x12, 0x00]).entry_point, 0x200);
+    }
+}
@@ -0,0 +1,71 @@
+//! Decoder, disassembler and emulator for the Chip-8 virtual machine.
+//!
+//! The [Emulator] executes programs one instruction or one 60 Hz frame at a time and
+//! exposes its state for frontends to display. [Opcode::decode] and [Disassembler] can
+//! be used on their own to inspect programs without running them.
+//!
+//! The decoder and emulator core build without the standard library or an allocator
+//! when the default `std` feature is disabled. Everything dealing with files, images
+//! and the other frontends, along with rewinding and save states, requires `std`.
+//!
+//! ```
+//! use chip8::{Emulator, Nibble, Opcode, Register};
+//!
+//! // LD V0, 0x2A; JP 0x202
+//! let program = [0x60, 0x2A, 0x12, 0x02];
+//! let v0 = Register(Nibble::from_low(0));
+//! assert_eq!(Opcode::decode(&program[..2]), Some(Opcode::LdImm(v0, 0x2A)));
+//!
+//! let mut emulator = Emulator::new();
+//! emulator.load(&program).unwrap();
+//! emulator.run_frame().unwrap();
+//! assert_eq!(emulator.registers()[0], 0x2A);
+//! ```
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+pub mod checksum;
+pub mod data;
+pub mod emulation;
+pub mod framebuffer;
+pub mod opcode;
+pub mod quirks;
+
+#[cfg(feature = "std")]
+pub mod audio;
+#[cfg(feature = "std")]
+pub mod coverage;
+#[cfg(feature = "std")]
+pub mod database;
+#[cfg(feature = "std")]
+pub mod debugger;
+#[cfg(feature = "std")]
+pub mod disassemble;
+#[cfg(feature = "std")]
+pub mod gif;
+#[cfg(feature = "std")]
+pub mod image;
+#[cfg(feature = "std")]
+pub mod inspect;
+#[cfg(feature = "std")]
+pub mod lint;
+#[cfg(feature = "std")]
+pub mod movie;
+#[cfg(feature = "std")]
+pub mod profile;
+#[cfg(feature = "std")]
+pub mod regression;
+#[cfg(feature = "std")]
+pub mod sprites;
+#[cfg(feature = "std")]
+pub mod symbols;
+#[cfg(feature = "std")]
+pub mod trace;
+
+pub use data::{Addr, Nibble, Register};
+#[cfg(feature = "std")]
+pub use disassemble::Disassembler;
+pub use emulation::{EmulationError, Emulator, Fault, Keypad, OverflowMode};
+pub use framebuffer::Framebuffer;
+pub use opcode::{Effects, Flow, Opcode, Operand};
+pub use quirks::{Platform, Quirks};
@@ -0,0 +1,1138 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display, Formatter},
+    io,
+};
+
+use crate::{
+    data::{Addr, Nibble},
+    emulation::{MAX_STACK_DEPTH, START_ADDRESS},
+    framebuffer::{HEIGHT, WIDTH},
+    opcode::{Flow, Opcode},
+    quirks::{Platform, Quirks},
+};
+
+/// [Severity] ranks diagnostics from informational notes to likely crashes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// [Diagnostic] is a single finding of the [Linter] about the instruction at `address`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Diagnostic {
+    pub address: Addr,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "0x{:03X}: {}: {}",
+            self.address, self.severity, self.message
+        )
+    }
+}
+
+/// [Draw] is a reachable `DRW` instruction whose sprite address in I is the same on
+/// every path to it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Draw {
+    pub address: Addr,
+    pub sprite: Addr,
+
+    /// Height of the sprite in rows; 0 draws a 16x16 sprite on SCHIP and XO-CHIP.
+    pub height: u8,
+}
+
+/// [QuirkUse] is an instruction whose effect depends on one of the [Quirks], named as in
+/// [Quirks::NAMES].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuirkUse {
+    pub address: Addr,
+    pub quirk: &'static str,
+    pub message: String,
+
+    /// Whether the program appears to rely on the quirk being set or unset, if the
+    /// instruction leans either way.
+    pub expects: Option<bool>,
+}
+
+impl Display for QuirkUse {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "0x{:03X}: {}: {}",
+            self.address, self.quirk, self.message
+        )
+    }
+}
+
+/// Groups the platforms which agree on every quirk in `quirks`, and on which a program
+/// depending only on those quirks therefore behaves the same.
+pub fn alike_platforms(quirks: &[&str]) -> Vec<Vec<Platform>> {
+    let mut groups: Vec<(Vec<bool>, Vec<Platform>)> = Vec::new();
+    for platform in Platform::ALL {
+        let flags = platform.quirks().flags();
+        let key: Vec<bool> = Quirks::NAMES
+            .iter()
+            .zip(flags)
+            .filter(|(name, _)| quirks.contains(name))
+            .map(|(_, set)| set)
+            .collect();
+
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, platforms)) => platforms.push(platform),
+            None => groups.push((key, vec![platform])),
+        }
+    }
+
+    groups.into_iter().map(|(_, platforms)| platforms).collect()
+}
+
+/// Recommends the platform whose quirks agree with the most quirk uses which lean one
+/// way, see [QuirkUse::expects], preferring the earliest of [Platform::ALL] on a tie.
+/// Returns None if no use leans either way.
+pub fn recommend_platform(uses: &[QuirkUse]) -> Option<Platform> {
+    let leaning: Vec<(usize, bool)> = uses
+        .iter()
+        .filter_map(|u| {
+            let index = Quirks::NAMES.iter().position(|name| *name == u.quirk)?;
+            Some((index, u.expects?))
+        })
+        .collect();
+
+    if leaning.is_empty() {
+        return None;
+    }
+
+    Platform::ALL.into_iter().rev().max_by_key(|platform| {
+        let flags = platform.quirks().flags();
+        leaning
+            .iter()
+            .filter(|&&(index, expects)| flags[index] == expects)
+            .count()
+    })
+}
+
+/// Writes one line per diagnostic prefixed with `name`, followed by the number of errors
+/// and warnings. Returns those numbers.
+pub fn write_report<W: io::Write>(
+    name: &str,
+    diagnostics: &[Diagnostic],
+    w: &mut W,
+) -> io::Result<(usize, usize)> {
+    for diagnostic in diagnostics {
+        writeln!(w, "{}: {}", name, diagnostic)?;
+    }
+
+    let count = |severity| {
+        diagnostics
+            .iter()
+            .filter(|d| d.severity == severity)
+            .count()
+    };
+    let (errors, warnings) = (count(Severity::Error), count(Severity::Warning));
+    writeln!(w, "{} errors, {} warnings", errors, warnings)?;
+
+    Ok((errors, warnings))
+}
+
+/// Writes one line per quirk dependent instruction prefixed with `name`, followed by the
+/// quirks the program depends on, the platforms on which it behaves alike and the one
+/// recommended by [recommend_platform] along with the instructions leaning towards it.
+pub fn write_quirk_report<W: io::Write>(
+    name: &str,
+    uses: &[QuirkUse],
+    w: &mut W,
+) -> io::Result<()> {
+    for quirk_use in uses {
+        writeln!(w, "{}: {}", name, quirk_use)?;
+    }
+
+    let quirks: Vec<&str> = Quirks::NAMES
+        .into_iter()
+        .filter(|name| uses.iter().any(|u| u.quirk == *name))
+        .collect();
+
+    if quirks.is_empty() {
+        return writeln!(
+            w,
+            "no quirk dependent instructions found, every platform should work"
+        );
+    }
+
+    writeln!(w, "depends on {}", quirks.join(", "))?;
+    for platforms in alike_platforms(&quirks) {
+        let names: Vec<String> = platforms.iter().map(|p| p.to_string()).collect();
+        writeln!(w, "behaves alike on {}", names.join(", "))?;
+    }
+
+    let platform = match recommend_platform(uses) {
+        Some(platform) => platform,
+        None => return writeln!(w, "no instruction suggests which platform is needed"),
+    };
+
+    let flags = platform.quirks().flags();
+    let reasons: Vec<String> = uses
+        .iter()
+        .filter_map(|u| {
+            let index = Quirks::NAMES.iter().position(|name| *name == u.quirk)?;
+            let expects = u.expects.filter(|&expects| flags[index] == expects)?;
+            let state = if expects { "on" } else { "off" };
+            Some(format!("{} {} at 0x{:03X}", u.quirk, state, u.address))
+        })
+        .collect();
+
+    writeln!(
+        w,
+        "recommended platform: {} ({})",
+        platform,
+        reasons.join(", ")
+    )
+}
+
+/// [Reachability] lists the words of a program which execution can reach.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Reachability {
+    /// Addresses of the reachable instructions.
+    pub instructions: BTreeSet<Addr>,
+
+    /// Addresses of reachable words which are not Chip-8 instructions, such as those of
+    /// the SUPER-CHIP and XO-CHIP extensions. Execution is not followed past them.
+    pub undecodable: BTreeSet<Addr>,
+}
+
+/// [Linter] looks for likely mistakes in Chip-8 programs by following their control flow
+/// from the start address without running them.
+///
+/// Only instructions reachable through direct jumps, calls, returns and skips are
+/// analysed. The targets of `JP V0, nnn` depend on run time values and are not followed.
+pub struct Linter {
+    quirks: Quirks,
+    stack_depth: usize,
+}
+
+impl Linter {
+    /// Constructs a linter for the default quirks and the deepest stack.
+    pub fn new() -> Self {
+        Linter {
+            quirks: Quirks::default(),
+            stack_depth: MAX_STACK_DEPTH,
+        }
+    }
+
+    /// Sets the quirks assumed when working out which state instructions use.
+    pub fn with_quirks(self, quirks: Quirks) -> Self {
+        Linter { quirks, ..self }
+    }
+
+    /// Sets the number of nested subroutine calls the stack can hold.
+    pub fn with_stack_depth(self, stack_depth: usize) -> Self {
+        Linter {
+            stack_depth,
+            ..self
+        }
+    }
+
+    /// Analyses a program and returns its diagnostics ordered by address.
+    pub fn lint(&self, program: &[u8]) -> Vec<Diagnostic> {
+        let mut analysis = Analysis::new(program, &self.quirks);
+        analysis.check_instructions();
+        analysis.check_unreachable();
+        analysis.check_subroutines(self.stack_depth);
+        analysis.check_dataflow();
+
+        let mut diagnostics = analysis.diagnostics;
+        diagnostics.sort();
+        diagnostics.dedup();
+        diagnostics
+    }
+
+    /// Finds the words of a program which execution can reach from the start address.
+    pub fn reachability(&self, program: &[u8]) -> Reachability {
+        let analysis = Analysis::new(program, &self.quirks);
+        Reachability {
+            instructions: analysis.reached.into_keys().collect(),
+            undecodable: analysis.undecodable,
+        }
+    }
+
+    /// Finds the reachable instructions whose effect on the program depends on the
+    /// quirks, ordered by address. The timing differences of `display_wait` affect every
+    /// program which draws and are not reported.
+    pub fn quirk_uses(&self, program: &[u8]) -> Vec<QuirkUse> {
+        let analysis = Analysis::new(program, &self.quirks);
+        analysis.quirk_uses()
+    }
+
+    /// Finds the reachable `DRW` instructions whose sprite address is known, ordered by
+    /// address.
+    pub fn draws(&self, program: &[u8]) -> Vec<Draw> {
+        let analysis = Analysis::new(program, &self.quirks);
+        let states = analysis.states();
+        states
+            .into_iter()
+            .filter_map(
+                |(address, state)| match (analysis.reached[&address], state.i) {
+                    (Opcode::Drw(_, _, height), Some(sprite)) => Some(Draw {
+                        address,
+                        sprite,
+                        height: height.as_u8(),
+                    }),
+                    _ => None,
+                },
+            )
+            .collect()
+    }
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// [Procedure] summarises a subroutine, or the main program, as seen from its entry.
+struct Procedure {
+    /// Subroutines called directly, mapped to the address of the first call.
+    callees: BTreeMap<Addr, Addr>,
+    returns: bool,
+    indirect: bool,
+}
+
+/// [State] is what is known about the machine before an instruction executes.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct State {
+    /// Bit mask of the registers written on every path to the instruction.
+    initialized: u16,
+
+    /// Value of I if it is the same on every path.
+    i: Option<Addr>,
+
+    /// Values of the registers which are the same on every path.
+    values: [Option<u8>; 16],
+}
+
+impl State {
+    const ENTRY: State = State {
+        initialized: 0,
+        i: None,
+        values: [None; 16],
+    };
+
+    fn meet(self, other: State) -> State {
+        let mut values = self.values;
+        for (value, other) in values.iter_mut().zip(other.values) {
+            if *value != other {
+                *value = None;
+            }
+        }
+
+        State {
+            initialized: self.initialized & other.initialized,
+            i: if self.i == other.i { self.i } else { None },
+            values,
+        }
+    }
+}
+
+struct Analysis<'a> {
+    program: &'a [u8],
+    quirks: &'a Quirks,
+
+    /// Reachable instructions by address.
+    reached: BTreeMap<Addr, Opcode>,
+
+    /// Reachable words which are not instructions.
+    undecodable: BTreeSet<Addr>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Analysis<'a> {
+    /// Constructs an analysis of `program` which has found every reachable instruction.
+    fn new(program: &'a [u8], quirks: &'a Quirks) -> Self {
+        let mut analysis = Analysis {
+            program,
+            quirks,
+            reached: BTreeMap::new(),
+            undecodable: BTreeSet::new(),
+            diagnostics: Vec::new(),
+        };
+
+        analysis.walk();
+        analysis
+    }
+
+    fn report(&mut self, address: Addr, severity: Severity, message: String) {
+        self.diagnostics.push(Diagnostic {
+            address,
+            severity,
+            message,
+        });
+    }
+
+    fn word(&self, address: Addr) -> Option<[u8; 2]> {
+        let offset = (address as usize).checked_sub(START_ADDRESS as usize)?;
+        let bytes = self.program.get(offset..offset + 2)?;
+        Some([bytes[0], bytes[1]])
+    }
+
+    /// Returns the addresses execution may continue at after `opcode`, following calls
+    /// into the subroutine if `into_calls` is set and assuming they return.
+    /// Addresses past the end of the address space are left out.
+    fn successors(address: Addr, opcode: &Opcode, into_calls: bool) -> Vec<Addr> {
+        let (next, skip) = (address.checked_add(2), address.checked_add(4));
+        match opcode.flow() {
+            Flow::Fallthrough => next.into_iter().collect(),
+            Flow::Jump(target) => vec![target],
+            Flow::Call(target) if into_calls => {
+                [Some(target), next].into_iter().flatten().collect()
+            }
+            Flow::Call(_) => next.into_iter().collect(),
+            Flow::Return | Flow::Indirect(_) => Vec::new(),
+            Flow::Skip => [next, skip].into_iter().flatten().collect(),
+        }
+    }
+
+    /// Finds every instruction reachable from the start address, reporting control flow
+    /// which leads outside of the program or into something other than instructions.
+    fn walk(&mut self) {
+        if self.program.len() < 2 {
+            return;
+        }
+
+        let mut pending = vec![(START_ADDRESS, START_ADDRESS)];
+        while let Some((from, address)) = pending.pop() {
+            if self.reached.contains_key(&address) {
+                continue;
+            }
+
+            let explicit = match self.reached.get(&from) {
+                Some(opcode) => {
+                    matches!(opcode.flow(), Flow::Jump(t) | Flow::Call(t) if t == address)
+                }
+                None => false,
+            };
+
+            if !address.is_multiple_of(2) {
+                let message = format!("jump to odd address 0x{:03X}", address);
+                self.report(from, Severity::Error, message);
+                continue;
+            }
+
+            let word = match self.word(address) {
+                Some(word) => word,
+                None if explicit => {
+                    let message = format!("jump to 0x{:03X} outside of the program", address);
+                    self.report(from, Severity::Error, message);
+                    continue;
+                }
+                None => {
+                    let message = String::from("execution can run past the end of the program");
+                    self.report(from, Severity::Warning, message);
+                    continue;
+                }
+            };
+
+            let opcode = match Opcode::decode(&word) {
+                Some(opcode) => opcode,
+                None if explicit => {
+                    self.undecodable.insert(address);
+                    let message = format!(
+                        "jump into data at 0x{:03X}, {:04X} is not an instruction",
+                        address,
+                        u16::from_be_bytes(word)
+                    );
+                    self.report(from, Severity::Error, message);
+                    continue;
+                }
+                None => {
+                    self.undecodable.insert(address);
+                    let message = format!(
+                        "execution can reach data, {:04X} is not an instruction",
+                        u16::from_be_bytes(word)
+                    );
+                    self.report(address, Severity::Error, message);
+                    continue;
+                }
+            };
+
+            // Successors past the end of the address space are not returned at all.
+            let wraps = match opcode.flow() {
+                Flow::Fallthrough | Flow::Call(_) => address.checked_add(2).is_none(),
+                Flow::Skip => address.checked_add(4).is_none(),
+                _ => false,
+            };
+
+            if wraps {
+                let message = String::from("execution can run past the end of the program");
+                self.report(address, Severity::Warning, message);
+            }
+
+            self.reached.insert(address, opcode);
+            for next in Self::successors(address, &opcode, true) {
+                pending.push((address, next));
+            }
+        }
+    }
+
+    fn check_instructions(&mut self) {
+        let reached: Vec<(Addr, Opcode)> = self.reached.iter().map(|(a, o)| (*a, *o)).collect();
+        for (address, opcode) in reached {
+            match opcode {
+                Opcode::Sys(target) => {
+                    let message = format!(
+                        "SYS 0x{:03X} calls machine code, which is not supported",
+                        target
+                    );
+                    self.report(address, Severity::Warning, message);
+                }
+
+                Opcode::JpV0(_) => {
+                    let message = String::from("the targets of this jump are not analysed");
+                    self.report(address, Severity::Info, message);
+                }
+
+                _ => {}
+            }
+        }
+    }
+
+    /// Reports bytes which are never executed, unless they are referenced by `LD I, nnn`
+    /// and therefore most likely data.
+    fn check_unreachable(&mut self) {
+        let mut covered = vec![false; self.program.len()];
+        let mut data = BTreeSet::new();
+        for (&address, opcode) in &self.reached {
+            let offset = (address - START_ADDRESS) as usize;
+            covered[offset] = true;
+            covered[offset + 1] = true;
+            if let Opcode::Ldi(target) = opcode {
+                data.insert(*target as usize);
+            }
+        }
+
+        let mut offset = 0;
+        while offset < covered.len() {
+            if covered[offset] {
+                offset += 1;
+                continue;
+            }
+
+            let start = offset;
+            while offset < covered.len() && !covered[offset] {
+                offset += 1;
+            }
+
+            let (first, last) = (
+                START_ADDRESS as usize + start,
+                START_ADDRESS as usize + offset,
+            );
+            let end = data.range(first..last).next().copied().unwrap_or(last);
+            if end > first {
+                let message = format!("{} unreachable bytes up to 0x{:03X}", end - first, end - 1);
+                self.report(first as Addr, Severity::Info, message);
+            }
+        }
+    }
+
+    /// Follows a subroutine or the main program from `entry`, stepping over calls.
+    fn procedure(&self, entry: Addr) -> Procedure {
+        let mut procedure = Procedure {
+            callees: BTreeMap::new(),
+            returns: false,
+            indirect: false,
+        };
+
+        let mut visited = BTreeSet::new();
+        let mut pending = vec![entry];
+        while let Some(address) = pending.pop() {
+            let opcode = match self.reached.get(&address) {
+                Some(opcode) if visited.insert(address) => opcode,
+                _ => continue,
+            };
+
+            match opcode.flow() {
+                Flow::Call(target) => {
+                    procedure.callees.entry(target).or_insert(address);
+                }
+                Flow::Return => procedure.returns = true,
+                Flow::Indirect(_) => procedure.indirect = true,
+                _ => {}
+            }
+
+            pending.extend(Self::successors(address, opcode, false));
+        }
+
+        procedure
+    }
+
+    fn check_subroutines(&mut self, stack_depth: usize) {
+        let mut procedures = BTreeMap::new();
+        let mut pending = vec![START_ADDRESS];
+        while let Some(entry) = pending.pop() {
+            if procedures.contains_key(&entry) || !self.reached.contains_key(&entry) {
+                continue;
+            }
+
+            let procedure = self.procedure(entry);
+            pending.extend(procedure.callees.keys());
+            procedures.insert(entry, procedure);
+        }
+
+        for (&entry, procedure) in &procedures {
+            if procedure.returns || procedure.indirect {
+                continue;
+            }
+
+            for (_, caller) in procedures.iter() {
+                if let Some(&site) = caller.callees.get(&entry) {
+                    let message = format!("subroutine at 0x{:03X} never returns", entry);
+                    self.report(site, Severity::Warning, message);
+                }
+            }
+        }
+
+        let mut depths = BTreeMap::new();
+        let mut active = Vec::new();
+        self.call_depth(START_ADDRESS, &procedures, &mut depths, &mut active);
+
+        // Follow the deepest chain of calls to the one which no longer fits on the stack.
+        let depth = depths[&START_ADDRESS];
+        let mut entry = START_ADDRESS;
+        for level in 1..=depth {
+            let deepest = procedures[&entry]
+                .callees
+                .iter()
+                .filter(|(callee, _)| depths.contains_key(callee))
+                .max_by_key(|(callee, _)| depths[callee]);
+
+            let Some((&callee, &site)) = deepest else {
+                break;
+            };
+
+            if level > stack_depth {
+                let message = format!(
+                    "calls nest {} deep but the stack only holds {}",
+                    depth, stack_depth
+                );
+                self.report(site, Severity::Error, message);
+                break;
+            }
+
+            entry = callee;
+        }
+    }
+
+    /// Returns the deepest nesting of calls made from `entry`, reporting recursion.
+    fn call_depth(
+        &mut self,
+        entry: Addr,
+        procedures: &BTreeMap<Addr, Procedure>,
+        depths: &mut BTreeMap<Addr, usize>,
+        active: &mut Vec<Addr>,
+    ) -> usize {
+        if let Some(&depth) = depths.get(&entry) {
+            return depth;
+        }
+
+        let Some(procedure) = procedures.get(&entry) else {
+            return 0;
+        };
+
+        active.push(entry);
+        let mut depth = 0;
+        for (&callee, &site) in &procedure.callees {
+            if active.contains(&callee) {
+                let message = format!("recursive call to 0x{:03X} may overflow the stack", callee);
+                self.report(site, Severity::Warning, message);
+                continue;
+            }
+
+            depth = depth.max(1 + self.call_depth(callee, procedures, depths, active));
+        }
+
+        active.pop();
+        depths.insert(entry, depth);
+        depth
+    }
+
+    /// Returns the state after executing `opcode` in `state`, not counting the effects
+    /// of subroutines.
+    fn transfer(&self, opcode: &Opcode, state: State) -> State {
+        let writes = opcode.writes(self.quirks);
+        let i = match (*opcode, state.i) {
+            (Opcode::Ldi(addr), _) => Some(addr),
+            (Opcode::Dump(r) | Opcode::Restore(r), Some(addr)) if writes.i => {
+                Some(addr.wrapping_add(r.0.as_u8() as Addr + 1))
+            }
+            _ if writes.i => None,
+            (_, i) => i,
+        };
+
+        let mut values = state.values;
+        for (x, value) in values.iter_mut().enumerate() {
+            if writes.registers & (1 << x) != 0 {
+                *value = None;
+            }
+        }
+
+        match *opcode {
+            Opcode::LdImm(r, x) => values[r.0.as_usize()] = Some(x),
+            Opcode::AddImm(r, x) => {
+                values[r.0.as_usize()] = state.values[r.0.as_usize()].map(|v| v.wrapping_add(x))
+            }
+            Opcode::Ld(r1, r2) => values[r1.0.as_usize()] = state.values[r2.0.as_usize()],
+            _ => {}
+        }
+
+        State {
+            initialized: state.initialized | writes.registers,
+            i,
+            values,
+        }
+    }
+
+    /// Runs a forward dataflow analysis from `entry`, returning the state before every
+    /// instruction visited. Calls are followed into subroutines if `into_calls` is set,
+    /// and their return sites continue with the registers in `written` added.
+    fn dataflow(
+        &self,
+        entry: Addr,
+        into_calls: bool,
+        written: &BTreeMap<Addr, u16>,
+    ) -> BTreeMap<Addr, State> {
+        let mut states: BTreeMap<Addr, State> = BTreeMap::new();
+        let mut pending = vec![(entry, State::ENTRY)];
+
+        while let Some((address, incoming)) = pending.pop() {
+            let Some(opcode) = self.reached.get(&address) else {
+                continue;
+            };
+
+            let state = match states.get(&address) {
+                Some(&state) if state == state.meet(incoming) => continue,
+                Some(&state) => state.meet(incoming),
+                None => incoming,
+            };
+
+            states.insert(address, state);
+            let after = self.transfer(opcode, state);
+            match opcode.flow() {
+                Flow::Call(target) => {
+                    if into_calls {
+                        pending.push((target, after));
+                    }
+
+                    let returned = State {
+                        initialized: after.initialized | written.get(&target).unwrap_or(&0),
+                        ..State::ENTRY
+                    };
+                    if let Some(next) = address.checked_add(2) {
+                        pending.push((next, returned));
+                    }
+                }
+
+                _ => {
+                    for next in Self::successors(address, opcode, false) {
+                        pending.push((next, after));
+                    }
+                }
+            }
+        }
+
+        states
+    }
+
+    /// Returns the registers written on every path through the subroutine at `entry`.
+    fn written(&self, entry: Addr, written: &mut BTreeMap<Addr, u16>) -> u16 {
+        if let Some(&registers) = written.get(&entry) {
+            return registers;
+        }
+
+        // Recursive calls are assumed to write nothing.
+        written.insert(entry, 0);
+        for callee in self.procedure(entry).callees.into_keys() {
+            self.written(callee, written);
+        }
+
+        let states = self.dataflow(entry, false, written);
+        let registers = states
+            .iter()
+            .filter(|(address, _)| self.reached[address] == Opcode::Ret)
+            .fold(u16::MAX, |registers, (_, state)| {
+                registers & state.initialized
+            });
+
+        written.insert(entry, registers);
+        registers
+    }
+
+    /// Returns the state before every reachable instruction.
+    fn states(&self) -> BTreeMap<Addr, State> {
+        let mut written = BTreeMap::new();
+        let callees: BTreeSet<Addr> = self
+            .reached
+            .values()
+            .filter_map(|opcode| match opcode.flow() {
+                Flow::Call(target) => Some(target),
+                _ => None,
+            })
+            .collect();
+
+        for callee in callees {
+            self.written(callee, &mut written);
+        }
+
+        self.dataflow(START_ADDRESS, true, &written)
+    }
+
+    /// Reports registers read before they are written and writes through I into the
+    /// interpreter area below the start address.
+    fn check_dataflow(&mut self) {
+        for (address, state) in self.states() {
+            let opcode = self.reached[&address];
+            let uninitialized = opcode.reads(self.quirks).registers & !state.initialized;
+            for x in (0..16).filter(|x| uninitialized & (1 << x) != 0) {
+                let message = format!("reads V{} before it is written", Nibble::from_low(x));
+                self.report(address, Severity::Warning, message);
+            }
+
+            if let (true, Some(i)) = (opcode.writes(self.quirks).memory, state.i) {
+                if i < START_ADDRESS {
+                    let message = format!("writes to 0x{:03X} in the interpreter area", i);
+                    self.report(address, Severity::Error, message);
+                }
+            }
+        }
+    }
+
+    /// Searches forward from the instruction at `address` for one which satisfies
+    /// `uses` before reaching one which satisfies `replaces`.
+    fn first_use<U, R>(&self, address: Addr, uses: U, replaces: R) -> Option<Addr>
+    where
+        U: Fn(&Opcode) -> bool,
+        R: Fn(&Opcode) -> bool,
+    {
+        let mut visited = BTreeSet::new();
+        let mut pending = Self::successors(address, &self.reached[&address], true);
+        while let Some(next) = pending.pop() {
+            let opcode = match self.reached.get(&next) {
+                Some(opcode) if visited.insert(next) => opcode,
+                _ => continue,
+            };
+
+            if uses(opcode) {
+                return Some(next);
+            }
+
+            if !replaces(opcode) {
+                pending.extend(Self::successors(next, opcode, true));
+            }
+        }
+
+        None
+    }
+
+    fn quirk_uses(&self) -> Vec<QuirkUse> {
+        let quirks = self.quirks;
+        let states = self.states();
+        let mut uses = Vec::new();
+        let mut report = |address, quirk, message, expects| {
+            uses.push(QuirkUse {
+                address,
+                quirk,
+                message,
+                expects,
+            })
+        };
+
+        // A quirk choosing between two registers leans towards the one which has been
+        // written when the other has not: true if that is `when_set`.
+        let lean = |address, when_set: usize, when_unset: usize| {
+            let initialized = states.get(&address)?.initialized;
+            match (initialized >> when_set & 1, initialized >> when_unset & 1) {
+                (1, 0) => Some(true),
+                (0, 1) => Some(false),
+                _ => None,
+            }
+        };
+
+        for (&address, opcode) in &self.reached {
+            match *opcode {
+                Opcode::Shr(r1, r2) | Opcode::Shl(r1, r2) if r1 != r2 => {
+                    let message = format!("shifts V{} instead of V{}", r2.0, r1.0);
+                    let expects = lean(address, r2.0.as_usize(), r1.0.as_usize());
+                    report(address, "shift_uses_vy", message, expects);
+                }
+
+                Opcode::Dump(_) | Opcode::Restore(_) => {
+                    let reads_i = |o: &Opcode| o.reads(quirks).i;
+                    let sets_i = |o: &Opcode| matches!(o, Opcode::Ldi(_) | Opcode::LdF(_));
+                    if let Some(user) = self.first_use(address, reads_i, sets_i) {
+                        let message =
+                            format!("leaves I advanced when it is used at 0x{:03X}", user);
+
+                        // Storing or loading the same registers again walks through a
+                        // table, while switching between the two reads back or updates
+                        // the bytes just accessed.
+                        let expects = match (opcode, self.reached[&user]) {
+                            (Opcode::Dump(_), Opcode::Dump(_))
+                            | (Opcode::Restore(_), Opcode::Restore(_)) => Some(true),
+                            (Opcode::Dump(_), Opcode::Restore(_))
+                            | (Opcode::Restore(_), Opcode::Dump(_)) => Some(false),
+                            _ => None,
+                        };
+                        report(address, "memory_increments_i", message, expects);
+                    }
+                }
+
+                Opcode::JpV0(target) if target >> 8 != 0 => {
+                    let message = format!("jumps relative to V{:x} instead of V0", target >> 8);
+                    let expects = lean(address, (target >> 8) as usize, 0);
+                    report(address, "jump_uses_vx", message, expects);
+                }
+
+                Opcode::Or(..) | Opcode::And(..) | Opcode::Xor(..) => {
+                    let unchanged = Quirks {
+                        vf_reset: false,
+                        ..*quirks
+                    };
+                    let reads_vf = |o: &Opcode| o.reads(quirks).vf();
+                    let sets_vf = |o: &Opcode| o.writes(&unchanged).vf();
+                    if let Some(user) = self.first_use(address, reads_vf, sets_vf) {
+                        let message = format!("resets VF which is read at 0x{:03X}", user);
+                        report(address, "vf_reset", message, None);
+                    }
+                }
+
+                Opcode::Drw(r1, r2, n) => {
+                    let values = states.get(&address).map(|state| state.values);
+                    let x = values.and_then(|v| v[r1.0.as_usize()]);
+                    let y = values.and_then(|v| v[r2.0.as_usize()]);
+                    let crosses_x = x.is_some_and(|x| x as usize % WIDTH > WIDTH - 8);
+                    let crosses_y = y.is_some_and(|y| y as usize % HEIGHT + n.as_usize() > HEIGHT);
+                    if crosses_x || crosses_y {
+                        let message = String::from("draws a sprite across the edge of the screen");
+                        report(address, "clip_sprites", message, None);
+                    }
+                }
+
+                _ => {}
+            }
+        }
+
+        uses
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn program(instructions: &[u16]) -> Vec<u8> {
+        instructions.iter().flat_map(|i| i.to_be_bytes()).collect()
+    }
+
+    fn lint(instructions: &[u16]) -> Vec<String> {
+        let program = program(instructions);
+        Linter::new()
+            .with_stack_depth(2)
+            .lint(&program)
+            .iter()
+            .map(|d| d.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn clean_program() {
+        // LD V0, 0x01; CALL 0x208; JP 0x204; (data); ADD V0, 0x01; RET
+        assert_eq!(
+            lint(&[0x6001, 0x2208, 0x1204, 0xFFFF, 0x7001, 0x00EE]),
+            ["0x206: info: 2 unreachable bytes up to 0x207"]
+        );
+    }
+
+    #[test]
+    fn control_flow() {
+        // JP 0x203; JP 0x206; SYS 0x123; (data)
+        assert_eq!(lint(&[0x1203]), ["0x200: error: jump to odd address 0x203"]);
+        assert_eq!(
+            lint(&[0x1204, 0x0000, 0xFFFF]),
+            [
+                "0x200: error: jump into data at 0x204, FFFF is not an instruction",
+                "0x202: info: 4 unreachable bytes up to 0x205"
+            ]
+        );
+        assert_eq!(
+            lint(&[0x0123]),
+            [
+                "0x200: warning: SYS 0x123 calls machine code, which is not supported",
+                "0x200: warning: execution can run past the end of the program"
+            ]
+        );
+    }
+
+    #[test]
+    fn subroutines() {
+        // CALL 0x204; JP 0x202; JP 0x204
+        assert_eq!(
+            lint(&[0x2204, 0x1202, 0x1204]),
+            ["0x200: warning: subroutine at 0x204 never returns"]
+        );
+
+        // CALL 0x204; JP 0x202; CALL 0x204; RET
+        assert_eq!(
+            lint(&[0x2204, 0x1202, 0x2204, 0x00EE]),
+            ["0x204: warning: recursive call to 0x204 may overflow the stack"]
+        );
+
+        // CALL 0x204; JP 0x202; CALL 0x208; RET; CALL 0x20C; RET; RET
+        assert_eq!(
+            lint(&[0x2204, 0x1202, 0x2208, 0x00EE, 0x220C, 0x00EE, 0x00EE]),
+            ["0x208: error: calls nest 3 deep but the stack only holds 2"]
+        );
+    }
+
+    #[test]
+    fn quirk_uses() {
+        // LD V3, 0x3C; SHR V1, V2; LD I, 0x300; LD [I], V1; DRW V3, V3, 5; OR V0, V1;
+        // SE VF, 0x00; JP V1, 0x100; JP 0x210
+        let program = program(&[
+            0x633C, 0x8126, 0xA300, 0xF155, 0xD335, 0x8011, 0x3F00, 0xB100, 0x1210,
+        ]);
+
+        let uses: Vec<String> = Linter::new()
+            .quirk_uses(&program)
+            .iter()
+            .map(|u| u.to_string())
+            .collect();
+
+        assert_eq!(
+            uses,
+            [
+                "0x202: shift_uses_vy: shifts V2 instead of V1",
+                "0x206: memory_increments_i: leaves I advanced when it is used at 0x208",
+                "0x208: clip_sprites: draws a sprite across the edge of the screen",
+                "0x20A: vf_reset: resets VF which is read at 0x20C",
+                "0x20E: jump_uses_vx: jumps relative to V1 instead of V0",
+            ]
+        );
+    }
+
+    #[test]
+    fn fills_address_space() {
+        // SE V0, 0x00 up to 0xFFFF, so the last instruction skips past the end of memory.
+        let program = program(&[0x3000; 0x7F00]);
+        let diagnostics: Vec<String> = Linter::new()
+            .lint(&program)
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+
+        for address in ["0xFFFC", "0xFFFE"] {
+            let message = format!(
+                "{}: warning: execution can run past the end of the program",
+                address
+            );
+            assert!(diagnostics.contains(&message));
+        }
+    }
+
+    #[test]
+    fn report() {
+        // SYS 0x123; JP 0x203
+        let diagnostics = Linter::new().lint(&program(&[0x0123, 0x1203]));
+
+        let mut out = Vec::new();
+        let counts = write_report("rom.ch8", &diagnostics, &mut out).unwrap();
+        assert_eq!(counts, (1, 1));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "rom.ch8: 0x200: warning: SYS 0x123 calls machine code, which is not supported\n\
+             rom.ch8: 0x202: error: jump to odd address 0x203\n\
+             1 errors, 1 warnings\n"
+        );
+    }
+
+    #[test]
+    fn recommendations() {
+        let uses = |instructions: &[u16]| Linter::new().quirk_uses(&program(instructions));
+
+        // LD V1, 0x04; SHR V1, V2; LD I, 0x300; LD [I], V1; LD V1, [I]; JP 0x20A
+        let schip = uses(&[0x6104, 0x8126, 0xA300, 0xF155, 0xF165, 0x120A]);
+        let expects: Vec<_> = schip.iter().map(|u| (u.quirk, u.expects)).collect();
+        assert_eq!(
+            expects,
+            [
+                ("shift_uses_vy", Some(false)),
+                ("memory_increments_i", Some(false))
+            ]
+        );
+        assert_eq!(recommend_platform(&schip), Some(Platform::Schip));
+
+        let mut out = Vec::new();
+        write_quirk_report("rom.ch8", &schip, &mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().ends_with(
+            "recommended platform: schip (shift_uses_vy off at 0x202, memory_increments_i off at 0x206)\n"
+        ));
+
+        // LD V2, 0x04; SHR V1, V2; LD I, 0x300; LD [I], V1; LD [I], V1; JP 0x20A
+        let vip = uses(&[0x6204, 0x8126, 0xA300, 0xF155, 0xF155, 0x120A]);
+        assert_eq!(recommend_platform(&vip), Some(Platform::Vip));
+
+        // LD V0, 0x02; JP V1, 0x100 on SCHIP needs V1 which is never written.
+        assert_eq!(
+            recommend_platform(&uses(&[0x6002, 0xB100])),
+            Some(Platform::Vip)
+        );
+
+        // SHR V1, V2 with neither register written.
+        assert_eq!(recommend_platform(&uses(&[0x8126, 0x1202])), None);
+    }
+
+    #[test]
+    fn platform_groups() {
+        use Platform::*;
+
+        assert_eq!(alike_platforms(&[]), [vec![Vip, Schip, XoChip]]);
+        assert_eq!(
+            alike_platforms(&["clip_sprites"]),
+            [vec![Vip, Schip], vec![XoChip]]
+        );
+        assert_eq!(
+            alike_platforms(&["shift_uses_vy"]),
+            [vec![Vip, XoChip], vec![Schip]]
+        );
+    }
+
+    #[test]
+    fn dataflow() {
+        // CALL 0x206; ADD V1, V2; JP 0x204; LD V1, 0x00; RET
+        assert_eq!(
+            lint(&[0x2206, 0x8124, 0x1204, 0x6100, 0x00EE]),
+            ["0x202: warning: reads V2 before it is written"]
+        );
+
+        // LD I, 0x050; LD V0, 0x01; LD [I], V0; JP 0x206
+        assert_eq!(
+            lint(&[0xA050, 0x6001, 0xF055, 0x1206]),
+            ["0x204: error: writes to 0x050 in the interpreter area"]
+        );
+    }
+}
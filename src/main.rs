@@ -1,17 +1,38 @@
-mod data;
-mod disassemble;
-mod emulation;
-mod opcode;
+mod terminal;
 
-use disassemble::Disassembler;
-use emulation::Emulator;
+use chip8::{
+    audio::Buzzer,
+    checksum::crc32,
+    coverage::{Coverage, SourceMap},
+    database::{Database, RomInfo},
+    debugger::Debugger,
+    disassemble::{Dialect, Format},
+    gif::GifRecorder,
+    image::{Color, ImageFormat, Palette, Screenshot},
+    inspect::Summary,
+    lint::{self, Linter},
+    movie::Movie,
+    profile::Profiler,
+    regression::Manifest,
+    sprites,
+    symbols::Symbols,
+    trace::{self, Trace, TraceRecord},
+    Disassembler, Emulator, OverflowMode, Platform,
+};
 use std::{
-    fs, io,
+    fmt::Display,
+    fs,
+    io::{self, BufReader, BufWriter, Write},
     path::{Path, PathBuf},
     process::exit,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use structopt::StructOpt;
+use terminal::Terminal;
 
+// The options are parsed once, so the size of the largest subcommand does not matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, StructOpt)]
 #[structopt(name = "chip8", about = "Chip8 Emulator")]
 enum Opt {
@@ -29,26 +50,447 @@ enum Opt {
         #[structopt(short = "b", long)]
         include_binary: bool,
 
+        /// Symbol file naming addresses, commenting instructions and declaring data.
+        #[structopt(short = "s", long)]
+        symbols: Option<PathBuf>,
+
+        /// Writes a symbol file of the jump and call targets and data regions found by
+        /// following the control flow, merged with those of --symbols, and uses it for
+        /// the listing.
+        #[structopt(long)]
+        emit_symbols: Option<PathBuf>,
+
+        /// Output format: text, or json with one object per instruction.
+        #[structopt(long, default_value = "text")]
+        format: Format,
+
+        /// Assembly syntax: cowgod, octo or chipper.
+        #[structopt(short = "d", long, default_value = "cowgod")]
+        dialect: Dialect,
+
         /// Path to the binary to execute.
         bin_path: PathBuf,
     },
 
     Run {
+        #[structopt(flatten)]
+        machine: MachineOpt,
+
+        /// Seed for the random number generator; defaults to the current time.
+        #[structopt(long)]
+        seed: Option<u32>,
+
+        /// Stops after executing this many instructions.
+        #[structopt(short = "c", long)]
+        cycles: Option<u64>,
+
+        /// Stops after this many 60 Hz frames.
+        #[structopt(short = "f", long)]
+        frames: Option<u64>,
+
+        /// Runs as fast as possible without a display or keyboard input. Requires
+        /// --cycles or --frames.
+        #[structopt(long)]
+        headless: bool,
+
+        /// Writes an execution trace to this file.
+        #[structopt(long)]
+        trace: Option<PathBuf>,
+
+        /// Restores the machine state from this file before running.
+        #[structopt(long)]
+        load_state: Option<PathBuf>,
+
+        /// Saves the machine state to this file once execution stops.
+        #[structopt(long)]
+        save_state: Option<PathBuf>,
+
+        /// Records keypad input to this movie file for later playback.
+        #[structopt(long)]
+        record_movie: Option<PathBuf>,
+
+        /// Saves an image of the display to this file once execution stops. The format
+        /// is picked from the extension: .png or .ppm.
+        #[structopt(long)]
+        screenshot: Option<PathBuf>,
+
+        /// Records the display to this file as an animated GIF.
+        #[structopt(long)]
+        record: Option<PathBuf>,
+
+        /// Renders the buzzer to this file as a WAV file.
+        #[structopt(long)]
+        audio_out: Option<PathBuf>,
+
+        /// Number of audio samples per second written with --audio-out.
+        #[structopt(long, default_value = "44100")]
+        sample_rate: u32,
+
+        /// Frequency in Hz of the buzzer tone.
+        #[structopt(long, default_value = "440")]
+        pitch: u32,
+
+        #[structopt(flatten)]
+        image: ImageOpt,
+
+        /// Path to the binary to execute.
+        bin_path: PathBuf,
+    },
+
+    /// Replays a recorded movie without a display.
+    Play {
+        /// Path to the movie to replay.
+        #[structopt(short = "m", long)]
+        movie: PathBuf,
+
+        /// Path to the binary the movie was recorded against.
+        bin_path: PathBuf,
+    },
+
+    /// Runs a program under an interactive debugger reading commands from stdin.
+    Debug {
+        #[structopt(flatten)]
+        machine: MachineOpt,
+
+        /// Seed for the random number generator; defaults to the current time.
+        #[structopt(long)]
+        seed: Option<u32>,
+
+        /// Number of instructions of history kept for stepping backwards.
+        #[structopt(long, default_value = "100000")]
+        history: usize,
+
         /// Path to the binary to execute.
         bin_path: PathBuf,
     },
+
+    /// Compares two execution traces and reports the first point at which they differ.
+    #[structopt(name = "trace-diff")]
+    TraceDiff {
+        /// Number of instructions to print before the divergence and after it in each trace.
+        #[structopt(long, default_value = "5")]
+        context: usize,
+
+        /// Path to the first trace.
+        a: PathBuf,
+
+        /// Path to the second trace.
+        b: PathBuf,
+    },
+
+    /// Runs the ROM regression tests listed in a TOML or JSON manifest.
+    Test {
+        /// Directory to write images of failing tests to; defaults to the directory of
+        /// the manifest.
+        #[structopt(long)]
+        diff_dir: Option<PathBuf>,
+
+        #[structopt(flatten)]
+        image: ImageOpt,
+
+        /// Path to the test manifest.
+        manifest: PathBuf,
+    },
+
+    /// Checks a program for likely mistakes without running it.
+    Lint {
+        /// Platform whose quirks to assume: vip, schip or xochip.
+        #[structopt(short = "p", long, default_value = "vip")]
+        platform: Platform,
+
+        /// Number of nested subroutine calls; defaults to 12 on vip and 16 otherwise.
+        #[structopt(long)]
+        stack_depth: Option<usize>,
+
+        /// Fails on warnings as well as errors.
+        #[structopt(long)]
+        deny_warnings: bool,
+
+        /// Path to the binary to check.
+        bin_path: PathBuf,
+    },
+
+    /// Prints a summary of a program: size, hashes, detected platform, instruction
+    /// histogram, code and data split, entry point, embedded text and what the ROM
+    /// database given with --database knows about it.
+    Info {
+        /// ROM database in the programs.json format of the community CHIP-8 database.
+        #[structopt(long)]
+        database: Option<PathBuf>,
+
+        /// Path to the binary to inspect.
+        bin_path: PathBuf,
+    },
+
+    /// Runs a program without a display and reports where its cycles are spent.
+    Profile {
+        #[structopt(flatten)]
+        machine: MachineOpt,
+
+        /// Seed for the random number generator; defaults to the current time.
+        #[structopt(long)]
+        seed: Option<u32>,
+
+        /// Number of instructions to execute.
+        #[structopt(short = "c", long, default_value = "100000")]
+        cycles: u64,
+
+        /// Number of entries to print in each section of the report.
+        #[structopt(long, default_value = "10")]
+        top: usize,
+
+        /// Writes the profile as folded stacks for flamegraph tools to this file.
+        #[structopt(long)]
+        folded: Option<PathBuf>,
+
+        /// Path to the binary to profile.
+        bin_path: PathBuf,
+    },
+
+    /// Runs a program without a display and prints which instructions were executed.
+    Coverage {
+        #[structopt(flatten)]
+        machine: MachineOpt,
+
+        /// Seed for the random number generator; defaults to the current time.
+        #[structopt(long)]
+        seed: Option<u32>,
+
+        /// Number of instructions to execute when no movie is given.
+        #[structopt(short = "c", long, default_value = "100000")]
+        cycles: u64,
+
+        /// Replays this movie instead of running for a number of cycles. May be given
+        /// several times to combine the coverage of multiple recordings.
+        #[structopt(short = "m", long = "movie", number_of_values = 1)]
+        movies: Vec<PathBuf>,
+
+        /// Source map of the assembled program, listing the source line of each address.
+        #[structopt(long)]
+        source_map: Option<PathBuf>,
+
+        /// Writes an LCOV tracefile to this path; requires --source-map.
+        #[structopt(long, requires = "source-map")]
+        lcov: Option<PathBuf>,
+
+        /// Path to the binary to run.
+        bin_path: PathBuf,
+    },
+
+    /// Finds the sprites a program draws and prints them as bitmaps or assembler data.
+    Sprites {
+        /// Draws pixels with Unicode blocks instead of # and . characters.
+        #[structopt(short = "u", long)]
+        unicode: bool,
+
+        /// Prints the sprites as assembler DB blocks instead of bitmaps.
+        #[structopt(long)]
+        db: bool,
+
+        /// Writes the sprites to this file as a PNG sheet.
+        #[structopt(long)]
+        png: Option<PathBuf>,
+
+        /// Platform the program is written for: vip, schip or xochip. Sprites drawn with
+        /// a height of zero are 16x16 on schip and xochip.
+        #[structopt(short = "p", long, default_value = "vip")]
+        platform: Platform,
+
+        #[structopt(flatten)]
+        image: ImageOpt,
+
+        /// Path to the binary to inspect.
+        bin_path: PathBuf,
+    },
+
+    /// Lists the instructions of a program which depend on quirks, the platforms on which
+    /// it behaves alike and the one it most likely needs.
+    Quirks {
+        /// Path to the binary to check.
+        bin_path: PathBuf,
+    },
+}
+
+/// Options selecting the machine a program runs on. Settings which are not given are
+/// taken from the ROM database given with --database if the program is in it.
+#[derive(Debug, StructOpt)]
+struct MachineOpt {
+    /// Platform whose quirks to emulate: vip, schip or xochip; defaults to vip.
+    #[structopt(short = "p", long)]
+    platform: Option<Platform>,
+
+    /// Number of instructions executed per 60 Hz frame; defaults to 10.
+    #[structopt(long)]
+    tick_rate: Option<u32>,
+
+    /// Number of nested subroutine calls; defaults to 12 on vip and 16 otherwise.
+    #[structopt(long)]
+    stack_depth: Option<usize>,
+
+    /// Behaviour of CALL with a full stack: error or wrap.
+    #[structopt(long, default_value = "error")]
+    stack_overflow: OverflowMode,
+
+    /// ROM database in the programs.json format of the community CHIP-8 database.
+    #[structopt(long)]
+    database: Option<PathBuf>,
+}
+
+/// [Machine] is the emulator set up for a program from the command line and the ROM
+/// database, along with the program's database entry if it is known.
+struct Machine {
+    emulator: Emulator,
+    info: Option<RomInfo>,
+}
+
+impl MachineOpt {
+    /// Configures an emulator with the settings known for `program` in the ROM database,
+    /// overridden by those given on the command line.
+    fn resolve(&self, program: &[u8]) -> Machine {
+        let database = open_database(self.database.as_deref());
+        let info = database.lookup(program).cloned();
+
+        let mut emulator = Emulator::new().with_stack_overflow(self.stack_overflow);
+        if let Some(info) = &info {
+            eprintln!(
+                "using the settings for {} from the ROM database",
+                info.title
+            );
+            emulator = info.configure(emulator);
+        }
+
+        if let Some(platform) = self.platform {
+            emulator = emulator
+                .with_quirks(platform.quirks())
+                .with_stack_depth(platform.stack_depth());
+        }
+
+        if let Some(tick_rate) = self.tick_rate {
+            emulator = emulator.with_tick_rate(tick_rate);
+        }
+
+        if let Some(stack_depth) = self.stack_depth {
+            emulator = emulator.with_stack_depth(stack_depth);
+        }
+
+        Machine { emulator, info }
+    }
+}
+
+/// Options controlling how the display is rendered into image files.
+#[derive(Debug, StructOpt)]
+struct ImageOpt {
+    /// Size in image pixels of each Chip-8 pixel.
+    #[structopt(long, default_value = "10")]
+    scale: usize,
+
+    /// Color of lit pixels as RRGGBB; defaults to ffffff or the ROM database's color.
+    #[structopt(long)]
+    foreground: Option<Color>,
+
+    /// Color of unlit pixels as RRGGBB; defaults to 000000 or the ROM database's color.
+    #[structopt(long)]
+    background: Option<Color>,
+}
+
+impl ImageOpt {
+    fn palette(&self) -> Palette {
+        self.palette_for(None)
+    }
+
+    /// Returns the palette given on the command line, taking any colors which are not
+    /// given from the ROM database entry `info`.
+    fn palette_for(&self, info: Option<&RomInfo>) -> Palette {
+        let palette = info.and_then(|info| info.palette).unwrap_or_default();
+        Palette {
+            foreground: self.foreground.unwrap_or(palette.foreground),
+            background: self.background.unwrap_or(palette.background),
+        }
+    }
+}
+
+/// Prints an error message and exits with a non-zero status.
+fn fail(message: impl Display) -> ! {
+    eprintln!("error: {}", message);
+    exit(1);
+}
+
+fn image_format(path: &Path) -> ImageFormat {
+    match ImageFormat::from_extension(path) {
+        Some(format) => format,
+        None => fail(format_args!("{}: unsupported image format", path.display())),
+    }
 }
 
 fn read_file(path: &Path) -> Vec<u8> {
     match fs::read(path) {
         Ok(content) => content,
-        Err(err) => {
-            eprintln!("{}", err);
-            exit(1);
+        Err(err) => fail(format_args!("{}: {}", path.display(), err)),
+    }
+}
+
+fn open_database(path: Option<&Path>) -> Database {
+    match path {
+        Some(path) => Database::read(path)
+            .unwrap_or_else(|err| fail(format_args!("{}: {}", path.display(), err))),
+        None => Database::default(),
+    }
+}
+
+fn read_trace(path: &Path) -> Trace {
+    let content = read_file(path);
+    match Trace::read(BufReader::new(content.as_slice())) {
+        Ok(trace) => trace,
+        Err(err) => fail(format_args!("{}: {}", path.display(), err)),
+    }
+}
+
+fn create_file(path: &Path) -> BufWriter<fs::File> {
+    match fs::File::create(path) {
+        Ok(file) => BufWriter::new(file),
+        Err(err) => fail(format_args!("{}: {}", path.display(), err)),
+    }
+}
+
+/// Creates a file and fills it using `write`, exiting with an error if anything fails.
+fn write_file<F>(path: &Path, write: F)
+where
+    F: FnOnce(&mut BufWriter<fs::File>) -> io::Result<()>,
+{
+    let mut w = create_file(path);
+    if let Err(err) = write(&mut w).and_then(|_| w.flush()) {
+        fail(format_args!("{}: {}", path.display(), err));
+    }
+}
+
+/// Resets the emulator and loads `program`, naming `path` in the error if it fails.
+fn load_program(emulator: &mut Emulator, program: &[u8], path: &Path) {
+    if let Err(err) = emulator.load(program) {
+        fail(format_args!("{}: {}", path.display(), err));
+    }
+}
+
+/// Executes a single instruction, appending it to `trace` if one is being written.
+fn step(emulator: &mut Emulator, trace: &mut Option<BufWriter<fs::File>>) -> Result<(), String> {
+    match trace {
+        Some(w) => {
+            let mut record = TraceRecord::capture(emulator);
+            let opcode = emulator.step().map_err(|err| err.to_string())?;
+            record.record_writes(opcode, emulator);
+            writeln!(w, "{}", record).map_err(|err| format!("trace: {}", err))
         }
+
+        None => emulator.step().map(|_| ()).map_err(|err| err.to_string()),
     }
 }
 
+fn default_seed() -> u32 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    now.subsec_nanos() ^ now.as_secs() as u32
+}
+
 fn main() {
     let opt = Opt::from_args();
     match opt {
@@ -56,22 +498,490 @@ fn main() {
             include_addresses,
             start_address,
             include_binary,
+            symbols,
+            emit_symbols,
+            format,
+            dialect,
             bin_path,
         } => {
             let program = read_file(&bin_path);
+            let symbols = match symbols {
+                Some(path) => {
+                    let text = String::from_utf8_lossy(&read_file(&path)).into_owned();
+                    Symbols::parse(&text)
+                        .unwrap_or_else(|err| fail(format_args!("{}: {}", path.display(), err)))
+                }
+                None => Symbols::new(),
+            };
+
+            let symbols = match emit_symbols {
+                Some(path) => {
+                    let mut generated = Symbols::generate(&program);
+                    generated.extend(symbols);
+                    write_file(&path, |w| write!(w, "{}", generated));
+                    generated
+                }
+                None => symbols,
+            };
 
-            Disassembler::new()
+            let result = Disassembler::new()
                 .with_addresses(include_addresses)
                 .with_start_address(start_address)
                 .with_binary(include_binary)
-                .disassemble(&program, &mut io::stdout())
-                .unwrap();
+                .with_symbols(symbols)
+                .with_format(format)
+                .with_dialect(dialect)
+                .disassemble(&program, &mut io::stdout());
+
+            if let Err(err) = result {
+                fail(format_args!("{}: {}", bin_path.display(), err));
+            }
+        }
+
+        Opt::Run {
+            machine,
+            seed,
+            cycles,
+            frames,
+            headless,
+            trace,
+            load_state,
+            save_state,
+            record_movie,
+            screenshot,
+            record,
+            audio_out,
+            sample_rate,
+            pitch,
+            image,
+            bin_path,
+        } => {
+            if headless && cycles.is_none() && frames.is_none() {
+                fail("--headless requires --cycles or --frames, as it never stops otherwise");
+            }
+
+            let program = read_file(&bin_path);
+            let screenshot = screenshot.map(|path| {
+                let format = image_format(&path);
+                (path, format)
+            });
+
+            let seed = seed.unwrap_or_else(default_seed);
+            let Machine { emulator, info } = machine.resolve(&program);
+            let mut emulator = emulator.with_seed(seed);
+            let palette = image.palette_for(info.as_ref());
+
+            load_program(&mut emulator, &program, &bin_path);
+            if let Some(path) = load_state {
+                if record_movie.is_some() {
+                    fail("movies must be recorded from the start of a program");
+                }
+
+                if let Err(err) = emulator.load_state(&read_file(&path)) {
+                    fail(format_args!("{}: {}", path.display(), err));
+                }
+            }
+
+            let mut trace = trace.map(|path| create_file(&path));
+            let mut movie = record_movie.as_ref().map(|_| Movie {
+                stack_depth: emulator.stack_capacity(),
+                stack_overflow: emulator.stack_overflow(),
+                ..Movie::new(
+                    crc32(&program),
+                    seed,
+                    emulator.tick_rate(),
+                    emulator.quirks(),
+                )
+            });
+
+            let mut terminal = if headless {
+                None
+            } else {
+                match Terminal::open() {
+                    Ok(terminal) => match &info {
+                        Some(info) => Some(terminal.with_keys(&info.keys)),
+                        None => Some(terminal),
+                    },
+                    Err(err) => fail(format_args!(
+                        "{} (use --headless to run without a display)",
+                        err
+                    )),
+                }
+            };
+
+            let mut gif = record.as_ref().map(|path| {
+                match GifRecorder::new(create_file(path), image.scale, palette) {
+                    Ok(gif) => gif,
+                    Err(err) => fail(format_args!("{}: {}", path.display(), err)),
+                }
+            });
+
+            let mut buzzer = audio_out.as_ref().map(|_| {
+                Buzzer::new()
+                    .with_sample_rate(sample_rate)
+                    .with_pitch(pitch)
+            });
+
+            let frame_time = Duration::from_secs(1) / 60;
+            let mut deadline = Instant::now();
+            let running = |emulator: &Emulator| {
+                cycles.is_none_or(|n| emulator.cycles() < n)
+                    && frames.is_none_or(|n| emulator.frames() < n)
+            };
+
+            // Errors are collected rather than reported straight away so that the
+            // terminal is restored and the outputs are still written after a fault.
+            let outcome = (|| -> Result<(), String> {
+                while running(&emulator) {
+                    if let Some(terminal) = &mut terminal {
+                        let mut keypad = emulator.keypad();
+                        if !terminal.poll(&mut keypad) {
+                            break;
+                        }
+
+                        emulator.set_keypad(keypad);
+                    }
+
+                    let (frame, keypad) = (emulator.frames(), emulator.keypad());
+                    while emulator.frames() == frame && running(&emulator) {
+                        step(&mut emulator, &mut trace)?;
+                    }
+
+                    // A frame cut short by --cycles is left out, as playback only runs
+                    // whole frames.
+                    if let (Some(movie), true) = (&mut movie, emulator.frames() > frame) {
+                        movie.record(frame, keypad);
+                    }
+
+                    if let (Some(gif), Some(path)) = (&mut gif, &record) {
+                        let result = gif.frame(emulator.framebuffer());
+                        result.map_err(|err| format!("{}: {}", path.display(), err))?;
+                    }
+
+                    if let Some(buzzer) = &mut buzzer {
+                        buzzer.frame(emulator.buzzer());
+                    }
+
+                    if let Some(terminal) = &mut terminal {
+                        let result = terminal.draw(emulator.framebuffer());
+                        result.map_err(|err| err.to_string())?;
+                        deadline += frame_time;
+                        thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                    }
+                }
+
+                Ok(())
+            })();
+
+            drop(terminal);
+            if let Err(message) = &outcome {
+                eprintln!("error: {}", message);
+            }
+
+            if let Some(w) = &mut trace {
+                if let Err(err) = w.flush() {
+                    fail(format_args!("trace: {}", err));
+                }
+            }
+
+            if let (Some(gif), Some(path)) = (gif, &record) {
+                if let Err(err) = gif.finish().and_then(|mut w| w.flush()) {
+                    fail(format_args!("{}: {}", path.display(), err));
+                }
+            }
+
+            if let (Some(path), Some(buzzer)) = (audio_out, buzzer) {
+                write_file(&path, |w| buzzer.write_wav(w));
+            }
+
+            if let (Some(path), Some(movie)) = (record_movie, movie) {
+                write_file(&path, |w| write!(w, "{}", movie));
+            }
+
+            if let Some(path) = save_state {
+                write_file(&path, |w| w.write_all(&emulator.save_state()));
+            }
+
+            if let Some((path, format)) = screenshot {
+                let screenshot = Screenshot::new()
+                    .with_scale(image.scale)
+                    .with_palette(palette);
+
+                write_file(&path, |w| {
+                    screenshot.write(emulator.framebuffer(), format, w)
+                });
+            }
+
+            if outcome.is_err() {
+                exit(1);
+            }
         }
 
-        Opt::Run { bin_path } => {
+        Opt::Play { movie, bin_path } => {
             let program = read_file(&bin_path);
+            let movie = match Movie::read(BufReader::new(read_file(&movie).as_slice())) {
+                Ok(movie) => movie,
+                Err(err) => fail(format_args!("{}: {}", movie.display(), err)),
+            };
+
+            if movie.rom != crc32(&program) {
+                eprintln!("warning: movie was recorded against a different ROM");
+            }
+
+            let mut emulator = movie.emulator();
+            load_program(&mut emulator, &program, &bin_path);
+            if let Err(err) = movie.play(&mut emulator, |_| {}) {
+                fail(err);
+            }
+
+            print!("{}", terminal::render_text(emulator.framebuffer()));
+            println!(
+                "Played {} frames ({} instructions)",
+                emulator.frames(),
+                emulator.cycles()
+            );
+        }
+
+        Opt::Debug {
+            machine,
+            seed,
+            history,
+            bin_path,
+        } => {
+            let program = read_file(&bin_path);
+            let mut emulator = machine
+                .resolve(&program)
+                .emulator
+                .with_seed(seed.unwrap_or_else(default_seed))
+                .with_rewind(history);
+
+            load_program(&mut emulator, &program, &bin_path);
+            let result = Debugger::new(emulator).run(io::stdin().lock(), &mut io::stdout());
+            if let Err(err) = result {
+                fail(err);
+            }
+        }
+
+        Opt::Profile {
+            machine,
+            seed,
+            cycles,
+            top,
+            folded,
+            bin_path,
+        } => {
+            let program = read_file(&bin_path);
+            let mut emulator = machine
+                .resolve(&program)
+                .emulator
+                .with_seed(seed.unwrap_or_else(default_seed));
+
+            load_program(&mut emulator, &program, &bin_path);
+            let mut profiler = Profiler::new();
+            while profiler.cycles() < cycles {
+                if let Err(err) = profiler.step(&mut emulator) {
+                    eprintln!("error: {}", err);
+                    break;
+                }
+            }
+
+            let result = profiler.write_report(emulator.memory(), top, &mut io::stdout());
+            if let Err(err) = result {
+                fail(err);
+            }
+
+            if let Some(path) = folded {
+                write_file(&path, |w| profiler.write_folded(w));
+            }
+        }
+
+        Opt::Coverage {
+            machine,
+            seed,
+            cycles,
+            movies,
+            source_map,
+            lcov,
+            bin_path,
+        } => {
+            let program = read_file(&bin_path);
+            let source_map = source_map.map(|path| {
+                let text = String::from_utf8_lossy(&read_file(&path)).into_owned();
+                SourceMap::parse(&text)
+                    .unwrap_or_else(|err| fail(format_args!("{}: {}", path.display(), err)))
+            });
+
+            let mut coverage = Coverage::new();
+            if movies.is_empty() {
+                let mut emulator = machine
+                    .resolve(&program)
+                    .emulator
+                    .with_seed(seed.unwrap_or_else(default_seed));
+
+                load_program(&mut emulator, &program, &bin_path);
+                while emulator.cycles() < cycles {
+                    if let Err(err) = coverage.step(&mut emulator) {
+                        eprintln!("error: {}", err);
+                        break;
+                    }
+                }
+            }
+
+            for path in &movies {
+                let movie = match Movie::read(BufReader::new(read_file(path).as_slice())) {
+                    Ok(movie) => movie,
+                    Err(err) => fail(format_args!("{}: {}", path.display(), err)),
+                };
+
+                if movie.rom != crc32(&program) {
+                    eprintln!(
+                        "warning: {} was recorded against a different ROM",
+                        path.display()
+                    );
+                }
+
+                let mut emulator = movie.emulator();
+                load_program(&mut emulator, &program, &bin_path);
+                let result = movie.play_with(&mut emulator, |e| coverage.step(e), |_| {});
+                if let Err(err) = result {
+                    eprintln!("error: {}: {}", path.display(), err);
+                }
+            }
+
+            if let Err(err) = coverage.write_listing(&program, &mut io::stdout()) {
+                fail(err);
+            }
+
+            if let (Some(path), Some(source_map)) = (lcov, source_map) {
+                write_file(&path, |w| coverage.write_lcov(&program, &source_map, w));
+            }
+        }
+
+        Opt::Sprites {
+            unicode,
+            db,
+            png,
+            platform,
+            image,
+            bin_path,
+        } => {
+            let program = read_file(&bin_path);
+            let linter = Linter::new().with_quirks(platform.quirks());
+            let sprites = sprites::find(&linter, &program, platform);
+            let result = if db {
+                sprites::write_db_blocks(&sprites, &mut io::stdout())
+            } else {
+                sprites::write_text(&sprites, unicode, &mut io::stdout())
+            };
+
+            if let Err(err) = result {
+                fail(err);
+            }
+
+            if sprites.is_empty() {
+                eprintln!("no sprites found");
+            }
+
+            if let Some(path) = png {
+                write_file(&path, |w| {
+                    sprites::write_sheet_png(&sprites, image.scale, image.palette(), w)
+                });
+            }
+        }
+
+        Opt::TraceDiff { context, a, b } => {
+            let trace_a = read_trace(&a);
+            let trace_b = read_trace(&b);
+
+            match trace::diff(&trace_a, &trace_b) {
+                Some(divergence) => {
+                    let mut stdout = io::stdout();
+                    let result =
+                        trace::write_report(&trace_a, &trace_b, &divergence, context, &mut stdout);
+
+                    if let Err(err) = result {
+                        fail(err);
+                    }
+
+                    exit(1);
+                }
+
+                None => println!("Traces match"),
+            }
+        }
+
+        Opt::Test {
+            diff_dir,
+            image,
+            manifest: path,
+        } => {
+            let manifest = match Manifest::read(&path) {
+                Ok(manifest) => manifest,
+                Err(err) => fail(format_args!("{}: {}", path.display(), err)),
+            };
+
+            let base = path.parent().unwrap_or(Path::new(""));
+            let diff_dir = diff_dir.as_deref().unwrap_or(base);
+            let screenshot = Screenshot::new()
+                .with_scale(image.scale)
+                .with_palette(image.palette());
+
+            match manifest.run(base, diff_dir, &screenshot, &mut io::stdout()) {
+                Ok(0) => {}
+                Ok(_) => exit(1),
+                Err(err) => fail(err),
+            }
+        }
+
+        Opt::Lint {
+            platform,
+            stack_depth,
+            deny_warnings,
+            bin_path,
+        } => {
+            let program = read_file(&bin_path);
+            let diagnostics = Linter::new()
+                .with_quirks(platform.quirks())
+                .with_stack_depth(stack_depth.unwrap_or_else(|| platform.stack_depth()))
+                .lint(&program);
+
+            let name = bin_path.display().to_string();
+            match lint::write_report(&name, &diagnostics, &mut io::stdout()) {
+                Ok((errors, warnings)) => {
+                    if errors > 0 || (deny_warnings && warnings > 0) {
+                        exit(1);
+                    }
+                }
+
+                Err(err) => fail(err),
+            }
+        }
+
+        Opt::Quirks { bin_path } => {
+            let program = read_file(&bin_path);
+            let uses = Linter::new().quirk_uses(&program);
+            let name = bin_path.display().to_string();
+            if let Err(err) = lint::write_quirk_report(&name, &uses, &mut io::stdout()) {
+                fail(err);
+            }
+        }
+
+        Opt::Info { database, bin_path } => {
+            let program = read_file(&bin_path);
+            let mut stdout = io::stdout();
+            if let Err(err) = Summary::new(&program).write_report(&mut stdout) {
+                fail(err);
+            }
+
+            let database = open_database(database.as_deref());
+            let result = match database.lookup(&program) {
+                Some(info) => info.write_report(&mut stdout),
+                None => writeln!(stdout, "not in the ROM database"),
+            };
 
-            Emulator::new().run(&program).unwrap();
+            if let Err(err) = result {
+                fail(err);
+            }
         }
     }
 }
@@ -0,0 +1,279 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    io::{self, BufRead},
+};
+
+use crate::{
+    emulation::{default_stack_depth, EmulationError, Emulator, Keypad, OverflowMode},
+    opcode::Opcode,
+    quirks::Quirks,
+};
+
+/// First line of every movie file.
+const HEADER: &str = "chip8-movie 1";
+
+/// [Movie] is a recording of the keypad over the course of a run along with everything
+/// else needed to replay it deterministically: the RNG seed, quirks, tick rate and stack
+/// configuration.
+///
+/// Movies are stored as text. After a header of `key value` lines, each line holds a
+/// frame number and the keypad state from that frame onwards as a hexadecimal bit mask:
+///
+/// ```text
+/// chip8-movie 1
+/// rom 5f3c29a1
+/// seed 1234
+/// tick-rate 10
+/// quirks vf_reset memory_increments_i display_wait clip_sprites shift_uses_vy
+/// stack-depth 12
+/// stack-overflow error
+/// frames 600
+/// 120 0020
+/// 126 0000
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    /// CRC-32 of the ROM the movie was recorded against.
+    pub rom: u32,
+    pub seed: u32,
+    pub tick_rate: u32,
+    pub quirks: Quirks,
+    pub stack_depth: usize,
+    pub stack_overflow: OverflowMode,
+
+    /// Total number of frames in the recording.
+    pub frames: u64,
+
+    /// Keypad changes as `(frame, keypad)` pairs in increasing frame order.
+    pub inputs: Vec<(u64, Keypad)>,
+}
+
+impl Movie {
+    /// Constructs an empty movie for a run with the given settings and the emulator's
+    /// default stack configuration for `quirks`.
+    pub fn new(rom: u32, seed: u32, tick_rate: u32, quirks: Quirks) -> Self {
+        Movie {
+            rom,
+            seed,
+            tick_rate,
+            quirks,
+            stack_depth: default_stack_depth(quirks),
+            stack_overflow: OverflowMode::Error,
+            frames: 0,
+            inputs: Vec::new(),
+        }
+    }
+
+    /// Records the keypad state at the start of a frame. Only changes are stored.
+    pub fn record(&mut self, frame: u64, keypad: Keypad) {
+        let last = self.inputs.last().map(|(_, k)| *k).unwrap_or_default();
+        if keypad != last {
+            self.inputs.push((frame, keypad));
+        }
+
+        self.frames = self.frames.max(frame + 1);
+    }
+
+    /// Constructs an emulator configured to replay this movie.
+    pub fn emulator(&self) -> Emulator {
+        Emulator::new()
+            .with_quirks(self.quirks)
+            .with_tick_rate(self.tick_rate)
+            .with_stack_depth(self.stack_depth)
+            .with_stack_overflow(self.stack_overflow)
+            .with_seed(self.seed)
+    }
+
+    /// Replays the movie on an emulator which has just loaded the program, calling
+    /// `on_frame` after every frame.
+    pub fn play<F>(&self, emulator: &mut Emulator, on_frame: F) -> Result<(), EmulationError>
+    where
+        F: FnMut(&Emulator),
+    {
+        self.play_with(emulator, Emulator::step, on_frame)
+    }
+
+    /// Replays the movie like [Movie::play], executing each instruction with `step` so
+    /// that callers can observe the run one instruction at a time.
+    pub fn play_with<S, F>(
+        &self,
+        emulator: &mut Emulator,
+        mut step: S,
+        mut on_frame: F,
+    ) -> Result<(), EmulationError>
+    where
+        S: FnMut(&mut Emulator) -> Result<Opcode, EmulationError>,
+        F: FnMut(&Emulator),
+    {
+        let mut inputs = self.inputs.iter().peekable();
+        for frame in 0..self.frames {
+            while let Some((_, keypad)) = inputs.next_if(|(f, _)| *f <= frame) {
+                emulator.set_keypad(*keypad);
+            }
+
+            let start = emulator.frames();
+            while emulator.frames() == start {
+                step(emulator)?;
+            }
+
+            on_frame(emulator);
+        }
+
+        Ok(())
+    }
+
+    /// Reads a movie from a reader.
+    pub fn read<R: BufRead>(r: R) -> Result<Self, MovieError> {
+        let mut lines = r.lines().enumerate();
+        let header = lines.next().map(|(_, line)| line).transpose()?;
+        if header.as_deref().map(str::trim) != Some(HEADER) {
+            return Err(MovieError::Parse(1, String::from("not a movie file")));
+        }
+
+        let mut movie = Movie::new(0, 0, 1, Quirks::default());
+        for (i, line) in lines {
+            let line = line?;
+            let err = |message: &str| MovieError::Parse(i + 1, String::from(message));
+            let mut words = line.split_whitespace();
+            let key = match words.next() {
+                Some(key) if !key.starts_with('#') => key,
+                _ => continue,
+            };
+
+            let value = words.next().unwrap_or("");
+            match key {
+                "rom" => movie.rom = u32::from_str_radix(value, 16).map_err(|_| err("bad rom"))?,
+                "seed" => movie.seed = value.parse().map_err(|_| err("bad seed"))?,
+                "tick-rate" => movie.tick_rate = value.parse().map_err(|_| err("bad tick rate"))?,
+                "stack-depth" => {
+                    movie.stack_depth = value.parse().map_err(|_| err("bad stack depth"))?
+                }
+                "stack-overflow" => {
+                    movie.stack_overflow = value.parse().map_err(|_| err("bad stack overflow"))?
+                }
+                "frames" => movie.frames = value.parse().map_err(|_| err("bad frame count"))?,
+                "quirks" => {
+                    let names: Vec<&str> = std::iter::once(value).chain(words).collect();
+                    if let Some(name) = names
+                        .iter()
+                        .find(|n| !n.is_empty() && !Quirks::NAMES.contains(n))
+                    {
+                        return Err(err(&format!("unknown quirk '{}'", name)));
+                    }
+
+                    movie.quirks = Quirks::from_flags(Quirks::NAMES.map(|q| names.contains(&q)));
+                }
+
+                _ => {
+                    let frame = key.parse().map_err(|_| err("bad frame number"))?;
+                    let bits =
+                        u16::from_str_radix(value, 16).map_err(|_| err("bad keypad state"))?;
+                    if movie.inputs.last().is_some_and(|(f, _)| *f > frame) {
+                        return Err(err("frames out of order"));
+                    }
+
+                    movie.inputs.push((frame, Keypad::from_bits(bits)));
+                }
+            }
+        }
+
+        Ok(movie)
+    }
+}
+
+impl Display for Movie {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "rom {:08x}", self.rom)?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "tick-rate {}", self.tick_rate)?;
+
+        let names: Vec<&str> = Quirks::NAMES
+            .iter()
+            .zip(self.quirks.flags())
+            .filter(|(_, set)| *set)
+            .map(|(name, _)| *name)
+            .collect();
+
+        writeln!(f, "quirks {}", names.join(" "))?;
+        writeln!(f, "stack-depth {}", self.stack_depth)?;
+        writeln!(f, "stack-overflow {}", self.stack_overflow)?;
+        writeln!(f, "frames {}", self.frames)?;
+        for (frame, keypad) in &self.inputs {
+            writeln!(f, "{} {:04x}", frame, keypad.bits())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum MovieError {
+    Io(io::Error),
+    Parse(usize, String),
+}
+
+impl Display for MovieError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MovieError::Io(err) => write!(f, "{}", err),
+            MovieError::Parse(line, message) => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+impl From<io::Error> for MovieError {
+    fn from(err: io::Error) -> Self {
+        MovieError::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::quirks::Platform;
+
+    #[test]
+    fn round_trip() {
+        let mut movie = Movie::new(0xDEADBEEF, 42, 15, Platform::Schip.quirks());
+        movie.stack_depth = 12;
+        movie.stack_overflow = OverflowMode::Wrap;
+        movie.record(0, Keypad::default());
+        movie.record(3, Keypad::from_bits(0x0020));
+        movie.record(4, Keypad::from_bits(0x0020));
+        movie.record(9, Keypad::default());
+
+        assert_eq!(movie.inputs.len(), 2);
+        assert_eq!(movie.frames, 10);
+
+        let text = movie.to_string();
+        assert_eq!(Movie::read(text.as_bytes()).unwrap(), movie);
+    }
+
+    #[test]
+    fn playback_is_deterministic() {
+        // RND V0, 0xFF; SKNP V0; ADD V2, 0x01; JP 0x200
+        let program = [0xC0, 0xFF, 0xE0, 0xA1, 0x72, 0x01, 0x12, 0x00];
+        let mut movie = Movie::new(0, 7, 10, Platform::Vip.quirks());
+        movie.frames = 30;
+
+        let play = |movie: &Movie| {
+            let mut emulator = movie.emulator();
+            emulator.load(&program).unwrap();
+            movie.play(&mut emulator, |_| {}).unwrap();
+            emulator
+        };
+
+        // Without input the key matching V0 is never down, so V2 is never incremented.
+        assert_eq!(play(&movie).registers()[2], 0);
+
+        movie.record(5, Keypad::from_bits(0xFFFF));
+        movie.record(20, Keypad::default());
+
+        let (a, b) = (play(&movie), play(&movie));
+        assert_eq!(a.save_state(), b.save_state());
+        assert_eq!(a.registers()[2], 37);
+    }
+}
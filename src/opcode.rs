@@ -1,6 +1,12 @@
-use std::fmt::{self, Display};
+use core::{
+    fmt::{self, Display},
+    ops::Deref,
+};
 
-use crate::data::{Addr, Nibble, Register};
+use crate::{
+    data::{Addr, Nibble, Register},
+    quirks::Quirks,
+};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Opcode {
@@ -26,9 +32,9 @@ pub enum Opcode {
     Xor(Register, Register),         // 8xy3 - Set Vx to Vx ^ Vy
     Add(Register, Register),         // 8xy4 - Set Vx to Vx + Vy and set VF = carry
     Sub(Register, Register),         // 8xy5 - Set Vx to Vx - Vy and set VF = NOT borrow
-    Shr(Register),                   // 8xy6 - Set Vx to Vx >> 1 and set VF = Vx & 0x01
+    Shr(Register, Register),         // 8xy6 - Set Vx to Vx >> 1 and set VF = Vx & 0x01
     Subn(Register, Register),        // 8xy7 - Set Vx to Vy - Vx and set FV = NOT borrow
-    Shl(Register),                   // 8xyE - Set Vx to Vx << 1 and set VF = Vx & 0x80
+    Shl(Register, Register),         // 8xyE - Set Vx to Vx << 1 and set VF = Vx & 0x80
     Snev(Register, Register),        // 9xy0 - Skip next instr. if Vx not equals Vy
     Ldi(Addr),                       // Annn - Set I to nnn
     JpV0(Addr),                      // Bnnn - Jump to V0 + nnn
@@ -118,9 +124,9 @@ impl Opcode {
                     0x3 => Some(Opcode::Xor(r1, r2)),
                     0x4 => Some(Opcode::Add(r1, r2)),
                     0x5 => Some(Opcode::Sub(r1, r2)),
-                    0x6 => Some(Opcode::Shr(r1)),
+                    0x6 => Some(Opcode::Shr(r1, r2)),
                     0x7 => Some(Opcode::Subn(r1, r2)),
-                    0xE => Some(Opcode::Shl(r1)),
+                    0xE => Some(Opcode::Shl(r1, r2)),
                     _ => None,
                 }
             }
@@ -187,50 +193,440 @@ impl Opcode {
     }
 }
 
-impl Display for Opcode {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl Opcode {
+    /// Returns the name of this instruction's variant, e.g. `LdImm`, which unlike the
+    /// mnemonic tells every kind of instruction apart.
+    pub fn name(&self) -> &'static str {
+        use Opcode::*;
+
+        match self {
+            Sys(_) => "Sys",
+            Cls => "Cls",
+            Ret => "Ret",
+            Jp(_) => "Jp",
+            Call(_) => "Call",
+            Se(..) => "Se",
+            Sne(..) => "Sne",
+            Sev(..) => "Sev",
+            LdImm(..) => "LdImm",
+            AddImm(..) => "AddImm",
+            Ld(..) => "Ld",
+            Or(..) => "Or",
+            And(..) => "And",
+            Xor(..) => "Xor",
+            Add(..) => "Add",
+            Sub(..) => "Sub",
+            Shr(..) => "Shr",
+            Subn(..) => "Subn",
+            Shl(..) => "Shl",
+            Snev(..) => "Snev",
+            Ldi(_) => "Ldi",
+            JpV0(_) => "JpV0",
+            Rnd(..) => "Rnd",
+            Drw(..) => "Drw",
+            Skp(_) => "Skp",
+            Sknp(_) => "Sknp",
+            LdVDt(_) => "LdVDt",
+            LdK(_) => "LdK",
+            LdDtV(_) => "LdDtV",
+            LdStV(_) => "LdStV",
+            AddI(_) => "AddI",
+            LdF(_) => "LdF",
+            LdB(_) => "LdB",
+            Dump(_) => "Dump",
+            Restore(_) => "Restore",
+        }
+    }
+
+    /// Returns the assembly mnemonic of this instruction, e.g. `LD` or `SKNP`.
+    pub fn mnemonic(&self) -> &'static str {
         use Opcode::*;
 
         match self {
-            Sys(addr) => write!(f, "SYS  0x{:03X}", addr),
-            Cls => write!(f, "CLS"),
-            Ret => write!(f, "RET"),
-            Jp(addr) => write!(f, "JP   0x{:03X}", addr),
-            Call(addr) => write!(f, "CALL 0x{:03X}", addr),
-            Se(r, x) => write!(f, "SE   V{}, 0x{:02X}", r.0, x),
-            Sne(r, x) => write!(f, "SNE  V{}, 0x{:02X}", r.0, x),
-            Sev(r1, r2) => write!(f, "SE   V{}, V{}", r1.0, r2.0),
-            LdImm(r, x) => write!(f, "LD   V{}, 0x{:02X}", r.0, x),
-            AddImm(r, x) => write!(f, "ADD  V{}, 0x{:02X}", r.0, x),
-            Ld(r1, r2) => write!(f, "LD   V{}, V{}", r1.0, r2.0),
-            Or(r1, r2) => write!(f, "OR   V{}, V{}", r1.0, r2.0),
-            And(r1, r2) => write!(f, "AND  V{}, V{}", r1.0, r2.0),
-            Xor(r1, r2) => write!(f, "XOR  V{}, V{}", r1.0, r2.0),
-            Add(r1, r2) => write!(f, "ADD  V{}, V{}", r1.0, r2.0),
-            Sub(r1, r2) => write!(f, "SUB  V{}, V{}", r1.0, r2.0),
-            Shr(r1) => write!(f, "SHR  V{}", r1.0),
-            Subn(r1, r2) => write!(f, "SUBN V{}, V{}", r1.0, r2.0),
-            Shl(r1) => write!(f, "SHL   V{}", r1.0),
-            Snev(r1, r2) => write!(f, "SNE  V{}, V{}", r1.0, r2.0),
-            Ldi(addr) => write!(f, "LD   I, 0x{:03X}", addr),
-            JpV0(addr) => write!(f, "JP   V0, 0x{:03X}", addr),
-            Rnd(r, x) => write!(f, "RND  V{}, 0x{:02X}", r.0, x),
-            Drw(r1, r2, n) => write!(f, "DRW  V{}, V{}, 0x{:X}", r1.0, r2.0, n.as_u8()),
-            Skp(r) => write!(f, "SKP  V{}", r.0),
-            Sknp(r) => write!(f, "SKNP V{}", r.0),
-            LdVDt(r) => write!(f, "LD   V{}, DT", r.0),
-            LdK(r) => write!(f, "LD   V{}, K", r.0),
-            LdDtV(r) => write!(f, "LD   DT, V{}", r.0),
-            LdStV(r) => write!(f, "LD   ST, V{}", r.0),
-            AddI(r) => write!(f, "ADD  I, V{}", r.0),
-            LdF(r) => write!(f, "LD   F, V{}", r.0),
-            LdB(r) => write!(f, "LD   B, V{}", r.0),
-            Dump(r) => write!(f, "LD   [I], V{}", r.0),
-            Restore(r) => write!(f, "LD   V{}, [I]", r.0),
+            Sys(_) => "SYS",
+            Cls => "CLS",
+            Ret => "RET",
+            Jp(_) | JpV0(_) => "JP",
+            Call(_) => "CALL",
+            Se(..) | Sev(..) => "SE",
+            Sne(..) | Snev(..) => "SNE",
+            LdImm(..) | Ld(..) | Ldi(_) | LdVDt(_) | LdK(_) | LdDtV(_) | LdStV(_) | LdF(_)
+            | LdB(_) | Dump(_) | Restore(_) => "LD",
+            AddImm(..) | Add(..) | AddI(_) => "ADD",
+            Or(..) => "OR",
+            And(..) => "AND",
+            Xor(..) => "XOR",
+            Sub(..) => "SUB",
+            Shr(..) => "SHR",
+            Subn(..) => "SUBN",
+            Shl(..) => "SHL",
+            Rnd(..) => "RND",
+            Drw(..) => "DRW",
+            Skp(_) => "SKP",
+            Sknp(_) => "SKNP",
+        }
+    }
+
+    /// Returns the operands of this instruction as written in assembly. `SHR` and `SHL`
+    /// only list Vx even though Vy is encoded as well, see [Opcode::reads].
+    pub fn operands(&self) -> Operands {
+        use Opcode::*;
+        use Operand::*;
+
+        let v = Operand::Register;
+        match *self {
+            Cls | Ret => Operands::new(&[]),
+            Sys(addr) | Jp(addr) | Call(addr) => Operands::new(&[Addr(addr)]),
+            Se(r, x) | Sne(r, x) | LdImm(r, x) | AddImm(r, x) | Rnd(r, x) => {
+                Operands::new(&[v(r), Byte(x)])
+            }
+            Sev(r1, r2)
+            | Snev(r1, r2)
+            | Ld(r1, r2)
+            | Or(r1, r2)
+            | And(r1, r2)
+            | Xor(r1, r2)
+            | Add(r1, r2)
+            | Sub(r1, r2)
+            | Subn(r1, r2) => Operands::new(&[v(r1), v(r2)]),
+            Shr(r, _) | Shl(r, _) | Skp(r) | Sknp(r) => Operands::new(&[v(r)]),
+            Ldi(addr) => Operands::new(&[I, Addr(addr)]),
+            JpV0(addr) => Operands::new(&[v(crate::data::Register::V0), Addr(addr)]),
+            Drw(r1, r2, n) => Operands::new(&[v(r1), v(r2), Nibble(n)]),
+            LdVDt(r) => Operands::new(&[v(r), DelayTimer]),
+            LdK(r) => Operands::new(&[v(r), Key]),
+            LdDtV(r) => Operands::new(&[DelayTimer, v(r)]),
+            LdStV(r) => Operands::new(&[SoundTimer, v(r)]),
+            AddI(r) => Operands::new(&[I, v(r)]),
+            LdF(r) => Operands::new(&[Font, v(r)]),
+            LdB(r) => Operands::new(&[Bcd, v(r)]),
+            Dump(r) => Operands::new(&[IndirectI, v(r)]),
+            Restore(r) => Operands::new(&[v(r), IndirectI]),
+        }
+    }
+
+    /// Returns the machine state read by this instruction when executed with `quirks`.
+    pub fn reads(&self, quirks: &Quirks) -> Effects {
+        use Opcode::*;
+
+        let none = Effects::default();
+        match *self {
+            Sys(_) | Cls | Jp(_) | Call(_) | LdImm(..) | Ldi(_) | Rnd(..) => none,
+            Ret => Effects {
+                stack: true,
+                ..none
+            },
+            Se(r, _) | Sne(r, _) | AddImm(r, _) | LdDtV(r) | LdStV(r) | LdF(r) => {
+                none.with_register(r)
+            }
+            Ld(_, r2) => none.with_register(r2),
+            Sev(r1, r2)
+            | Snev(r1, r2)
+            | Or(r1, r2)
+            | And(r1, r2)
+            | Xor(r1, r2)
+            | Add(r1, r2)
+            | Sub(r1, r2)
+            | Subn(r1, r2) => none.with_register(r1).with_register(r2),
+            Shr(r1, r2) | Shl(r1, r2) => {
+                none.with_register(if quirks.shift_uses_vy { r2 } else { r1 })
+            }
+            JpV0(addr) => {
+                let x = if quirks.jump_uses_vx {
+                    (addr >> 8) as u8
+                } else {
+                    0
+                };
+                none.with_register(Register(Nibble::from_low(x)))
+            }
+            Drw(r1, r2, _) => Effects {
+                i: true,
+                memory: true,
+                display: true,
+                ..none.with_register(r1).with_register(r2)
+            },
+            Skp(r) | Sknp(r) => Effects {
+                keypad: true,
+                ..none.with_register(r)
+            },
+            LdVDt(_) => Effects {
+                delay_timer: true,
+                ..none
+            },
+            LdK(_) => Effects {
+                keypad: true,
+                ..none
+            },
+            AddI(r) | LdB(r) => Effects {
+                i: true,
+                ..none.with_register(r)
+            },
+            Dump(r) => Effects {
+                i: true,
+                ..none.with_registers_to(r)
+            },
+            Restore(_) => Effects {
+                i: true,
+                memory: true,
+                ..none
+            },
+        }
+    }
+
+    /// Returns the machine state written by this instruction when executed with
+    /// `quirks`. Changes to the program counter are described by [Opcode::flow].
+    pub fn writes(&self, quirks: &Quirks) -> Effects {
+        use Opcode::*;
+
+        let none = Effects::default();
+        match *self {
+            Sys(_) | Jp(_) | Se(..) | Sne(..) | Sev(..) | Snev(..) | JpV0(_) | Skp(_) | Sknp(_) => {
+                none
+            }
+            Cls => Effects {
+                display: true,
+                ..none
+            },
+            Ret | Call(_) => Effects {
+                stack: true,
+                ..none
+            },
+            LdImm(r, _) | AddImm(r, _) | Ld(r, _) | Rnd(r, _) | LdVDt(r) | LdK(r) => {
+                none.with_register(r)
+            }
+            Or(r, _) | And(r, _) | Xor(r, _) if quirks.vf_reset => {
+                none.with_register(r).with_register(Register::VF)
+            }
+            Or(r, _) | And(r, _) | Xor(r, _) => none.with_register(r),
+            Add(r, _) | Sub(r, _) | Subn(r, _) | Shr(r, _) | Shl(r, _) => {
+                none.with_register(r).with_register(Register::VF)
+            }
+            Ldi(_) | AddI(_) | LdF(_) => Effects { i: true, ..none },
+            Drw(..) => Effects {
+                display: true,
+                ..none.with_register(Register::VF)
+            },
+            LdDtV(_) => Effects {
+                delay_timer: true,
+                ..none
+            },
+            LdStV(_) => Effects {
+                sound_timer: true,
+                ..none
+            },
+            LdB(_) => Effects {
+                memory: true,
+                ..none
+            },
+            Dump(_) => Effects {
+                i: quirks.memory_increments_i,
+                memory: true,
+                ..none
+            },
+            Restore(r) => Effects {
+                i: quirks.memory_increments_i,
+                ..none.with_registers_to(r)
+            },
+        }
+    }
+
+    /// Returns how this instruction affects the program counter.
+    pub fn flow(&self) -> Flow {
+        use Opcode::*;
+
+        match *self {
+            Jp(addr) => Flow::Jump(addr),
+            Call(addr) => Flow::Call(addr),
+            Ret => Flow::Return,
+            Se(..) | Sne(..) | Sev(..) | Snev(..) | Skp(_) | Sknp(_) => Flow::Skip,
+            JpV0(addr) => Flow::Indirect(addr),
+            _ => Flow::Fallthrough,
         }
     }
 }
 
+/// [Effects] is the set of machine state read or written by an instruction, as
+/// returned by [Opcode::reads] and [Opcode::writes].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Effects {
+    /// Bit mask of the general purpose registers with bit `n` set for Vn. VF is bit 15.
+    pub registers: u16,
+
+    /// The address register I.
+    pub i: bool,
+
+    /// Memory at the address in I.
+    pub memory: bool,
+
+    /// The delay timer DT.
+    pub delay_timer: bool,
+
+    /// The sound timer ST.
+    pub sound_timer: bool,
+
+    /// The pixels of the display.
+    pub display: bool,
+
+    /// The state of the keys on the keypad.
+    pub keypad: bool,
+
+    /// The return address stack.
+    pub stack: bool,
+}
+
+impl Effects {
+    /// Returns true if the register `r` is included.
+    pub fn register(&self, r: Register) -> bool {
+        self.registers & (1 << r.0.as_u8()) != 0
+    }
+
+    /// Returns true if the flag register VF is included.
+    pub fn vf(&self) -> bool {
+        self.register(Register::VF)
+    }
+
+    /// Returns true if no state at all is included.
+    pub fn is_empty(&self) -> bool {
+        *self == Effects::default()
+    }
+
+    fn with_register(self, r: Register) -> Self {
+        Effects {
+            registers: self.registers | 1 << r.0.as_u8(),
+            ..self
+        }
+    }
+
+    /// Includes the registers V0 to `r` inclusive.
+    fn with_registers_to(self, r: Register) -> Self {
+        Effects {
+            registers: self.registers | (2u32 << r.0.as_u8()).wrapping_sub(1) as u16,
+            ..self
+        }
+    }
+}
+
+/// [Flow] classifies the effect of an instruction on the program counter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flow {
+    /// Continues with the next instruction. `LD Vx, K` repeats until a key is pressed.
+    Fallthrough,
+
+    /// Continues at a fixed address.
+    Jump(Addr),
+
+    /// Calls the subroutine at a fixed address, returning to the next instruction.
+    Call(Addr),
+
+    /// Continues at the return address on top of the stack.
+    Return,
+
+    /// Continues with either the next instruction or the one after it.
+    Skip,
+
+    /// Jumps to the given base address plus a register only known at run time.
+    Indirect(Addr),
+}
+
+/// [Operand] is a single operand of an instruction as written in assembly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operand {
+    /// A general purpose register, `Vx`.
+    Register(Register),
+
+    /// An 8-bit immediate value.
+    Byte(u8),
+
+    /// A 4-bit immediate value.
+    Nibble(Nibble),
+
+    /// A 12-bit memory address.
+    Addr(Addr),
+
+    /// The address register, `I`.
+    I,
+
+    /// Memory starting at the address in I, `[I]`.
+    IndirectI,
+
+    /// The delay timer, `DT`.
+    DelayTimer,
+
+    /// The sound timer, `ST`.
+    SoundTimer,
+
+    /// The next key press, `K`.
+    Key,
+
+    /// The location of a font sprite, `F`.
+    Font,
+
+    /// The BCD representation of a register, `B`.
+    Bcd,
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Register(r) => write!(f, "V{}", r.0),
+            Operand::Byte(x) => write!(f, "0x{:02X}", x),
+            Operand::Nibble(n) => write!(f, "0x{:X}", n.as_u8()),
+            Operand::Addr(addr) => write!(f, "0x{:03X}", addr),
+            Operand::I => write!(f, "I"),
+            Operand::IndirectI => write!(f, "[I]"),
+            Operand::DelayTimer => write!(f, "DT"),
+            Operand::SoundTimer => write!(f, "ST"),
+            Operand::Key => write!(f, "K"),
+            Operand::Font => write!(f, "F"),
+            Operand::Bcd => write!(f, "B"),
+        }
+    }
+}
+
+/// [Operands] holds the up to three operands of an instruction and dereferences to a
+/// slice of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Operands {
+    operands: [Operand; 3],
+    len: usize,
+}
+
+impl Operands {
+    fn new(operands: &[Operand]) -> Self {
+        let mut array = [Operand::I; 3];
+        array[..operands.len()].copy_from_slice(operands);
+        Operands {
+            operands: array,
+            len: operands.len(),
+        }
+    }
+}
+
+impl Deref for Operands {
+    type Target = [Operand];
+
+    fn deref(&self) -> &[Operand] {
+        &self.operands[..self.len]
+    }
+}
+
+impl Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operands = self.operands();
+        if operands.is_empty() {
+            return write!(f, "{}", self.mnemonic());
+        }
+
+        write!(f, "{:<4}", self.mnemonic())?;
+        for (i, operand) in operands.iter().enumerate() {
+            let separator = if i == 0 { " " } else { ", " };
+            write!(f, "{}{}", separator, operand)?;
+        }
+
+        Ok(())
+    }
+}
+
 fn addr_from_bytes(high: u8, low: u8) -> Addr {
     u16::from_be_bytes([high & 0x0F, low])
 }
@@ -256,4 +652,97 @@ mod test {
         let opcode = Opcode::decode(&[0x00, 0xEE]);
         assert_eq!(opcode, Some(Opcode::Ret));
     }
+
+    #[test]
+    fn operands() {
+        let (v1, v2) = (Register(Nibble::from_low(1)), Register(Nibble::from_low(2)));
+        let drw = Opcode::Drw(v1, v2, Nibble::from_low(5));
+        assert_eq!(drw.mnemonic(), "DRW");
+        assert_eq!(
+            *drw.operands(),
+            [
+                Operand::Register(v1),
+                Operand::Register(v2),
+                Operand::Nibble(Nibble::from_low(5))
+            ]
+        );
+
+        assert_eq!(Opcode::Dump(v2).mnemonic(), "LD");
+        assert_eq!(
+            *Opcode::Dump(v2).operands(),
+            [Operand::IndirectI, Operand::Register(v2)]
+        );
+        assert!(Opcode::Cls.operands().is_empty());
+    }
+
+    #[test]
+    fn effects_depend_on_quirks() {
+        use crate::quirks::Platform;
+
+        let (vip, schip) = (Platform::Vip.quirks(), Platform::Schip.quirks());
+        let (v1, v2) = (Register(Nibble::from_low(1)), Register(Nibble::from_low(2)));
+
+        let shr = Opcode::Shr(v1, v2);
+        assert_eq!(shr.reads(&vip).registers, 1 << 2);
+        assert_eq!(shr.reads(&schip).registers, 1 << 1);
+        assert_eq!(shr.writes(&vip).registers, 1 << 1 | 1 << 15);
+
+        assert!(Opcode::Or(v1, v2).writes(&vip).vf());
+        assert!(!Opcode::Or(v1, v2).writes(&schip).vf());
+
+        let restore = Opcode::Restore(v2);
+        assert_eq!(
+            restore.reads(&vip),
+            Effects {
+                i: true,
+                memory: true,
+                ..Effects::default()
+            }
+        );
+        assert_eq!(restore.writes(&vip).registers, 0b111);
+        assert!(restore.writes(&vip).i && !restore.writes(&schip).i);
+
+        assert!(Opcode::JpV0(0x300).reads(&vip).register(Register::V0));
+        assert!(Opcode::JpV0(0x300)
+            .reads(&schip)
+            .register(Register(Nibble::from_low(3))));
+        assert!(Opcode::Jp(0x300).reads(&vip).is_empty());
+    }
+
+    #[test]
+    fn flow() {
+        let v1 = Register(Nibble::from_low(1));
+        assert_eq!(Opcode::Jp(0x208).flow(), Flow::Jump(0x208));
+        assert_eq!(Opcode::Call(0x300).flow(), Flow::Call(0x300));
+        assert_eq!(Opcode::Ret.flow(), Flow::Return);
+        assert_eq!(Opcode::Sknp(v1).flow(), Flow::Skip);
+        assert_eq!(Opcode::JpV0(0x300).flow(), Flow::Indirect(0x300));
+        assert_eq!(Opcode::LdK(v1).flow(), Flow::Fallthrough);
+    }
+
+    /// Checks the decoding and formatting of every possible instruction word against the
+    /// snapshot in `snapshots/decode.txt`. Run with `UPDATE_SNAPSHOTS=1` to rewrite the
+    /// snapshot after an intentional change.
+    #[test]
+    fn decode_snapshot() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/snapshots/decode.txt");
+        let actual: String = (0..=u16::MAX)
+            .map(|word| match Opcode::decode(&word.to_be_bytes()) {
+                Some(opcode) => format!("{:04X} {}\n", word, opcode),
+                None => format!("{:04X} invalid\n", word),
+            })
+            .collect();
+
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(path, &actual).unwrap();
+            return;
+        }
+
+        let expected = std::fs::read_to_string(path).unwrap();
+        for (actual, expected) in actual.lines().zip(expected.lines()) {
+            assert_eq!(actual, expected, "decoding differs from the snapshot");
+        }
+
+        assert_eq!(actual.lines().count(), expected.lines().count());
+    }
 }
@@ -0,0 +1,297 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+};
+
+use crate::{
+    data::Addr,
+    emulation::{EmulationError, Emulator},
+    opcode::{Flow, Opcode},
+};
+
+/// [Profiler] counts where an emulator spends its cycles: how often each address is
+/// executed, how many cycles each subroutine takes and which loops run hottest.
+///
+/// Subroutines are tracked by mirroring the `CALL` and `RET` instructions executed on
+/// the emulator's stack, so the profile can also be written as folded stacks for
+/// flamegraph tools.
+#[derive(Clone, Debug, Default)]
+pub struct Profiler {
+    /// Number of executions of each address.
+    counts: BTreeMap<Addr, u64>,
+
+    /// Entry points of the subroutines currently being executed, outermost first.
+    calls: Vec<Addr>,
+
+    /// Number of cycles spent in each chain of calls.
+    stacks: BTreeMap<Vec<Addr>, u64>,
+
+    /// Number of times each backward jump was taken, keyed by source and target.
+    back_edges: BTreeMap<(Addr, Addr), u64>,
+
+    subroutines: BTreeMap<Addr, Subroutine>,
+    cycles: u64,
+}
+
+/// [Subroutine] holds the profile of the code called at a single address.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Subroutine {
+    pub calls: u64,
+
+    /// Cycles spent in the subroutine itself.
+    pub self_cycles: u64,
+
+    /// Cycles spent in the subroutine and everything it calls.
+    pub total_cycles: u64,
+}
+
+/// [Loop] is the code between the target and the source of a backward jump.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Loop {
+    pub start: Addr,
+
+    /// Address of the jump closing the loop.
+    pub end: Addr,
+
+    /// Number of times the jump was taken.
+    pub iterations: u64,
+
+    /// Cycles spent on the instructions inside the loop.
+    pub cycles: u64,
+}
+
+impl Profiler {
+    /// Constructs an empty profiler.
+    pub fn new() -> Self {
+        Profiler::default()
+    }
+
+    /// Executes a single instruction on `emulator`, recording it in the profile.
+    pub fn step(&mut self, emulator: &mut Emulator) -> Result<Opcode, EmulationError> {
+        let pc = emulator.program_counter();
+        let opcode = emulator.step()?;
+
+        self.cycles += 1;
+        *self.counts.entry(pc).or_insert(0) += 1;
+        *self.stacks.entry(self.calls.clone()).or_insert(0) += 1;
+
+        if let Some(&current) = self.calls.last() {
+            self.subroutines.entry(current).or_default().self_cycles += 1;
+        }
+
+        let active: BTreeSet<Addr> = self.calls.iter().copied().collect();
+        for address in active {
+            self.subroutines.entry(address).or_default().total_cycles += 1;
+        }
+
+        match opcode.flow() {
+            Flow::Call(target) => {
+                self.calls.push(target);
+                self.subroutines.entry(target).or_default().calls += 1;
+            }
+
+            Flow::Return => {
+                self.calls.pop();
+            }
+
+            Flow::Jump(_) | Flow::Indirect(_) => {
+                let target = emulator.program_counter();
+                if target <= pc {
+                    *self.back_edges.entry((pc, target)).or_insert(0) += 1;
+                }
+            }
+
+            Flow::Fallthrough | Flow::Skip => {}
+        }
+
+        Ok(opcode)
+    }
+
+    /// Returns the number of instructions profiled.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Returns the number of executions of each address.
+    pub fn counts(&self) -> &BTreeMap<Addr, u64> {
+        &self.counts
+    }
+
+    /// Returns the profile of every subroutine called, keyed by entry point.
+    pub fn subroutines(&self) -> &BTreeMap<Addr, Subroutine> {
+        &self.subroutines
+    }
+
+    /// Returns the addresses executed most often, hottest first.
+    pub fn hotspots(&self) -> Vec<(Addr, u64)> {
+        let mut hotspots: Vec<(Addr, u64)> = self.counts.iter().map(|(&a, &n)| (a, n)).collect();
+        hotspots.sort_by_key(|&(address, count)| (std::cmp::Reverse(count), address));
+        hotspots
+    }
+
+    /// Returns the loops closed by the backward jumps taken, hottest first.
+    pub fn loops(&self) -> Vec<Loop> {
+        let mut loops: Vec<Loop> = self
+            .back_edges
+            .iter()
+            .map(|(&(end, start), &iterations)| Loop {
+                start,
+                end,
+                iterations,
+                cycles: self.counts.range(start..=end).map(|(_, &n)| n).sum(),
+            })
+            .collect();
+
+        loops.sort_by_key(|l| (std::cmp::Reverse(l.cycles), l.start));
+        loops
+    }
+
+    /// Writes the profile as folded stacks, one line per chain of calls with the
+    /// number of cycles spent in it, as read by `flamegraph.pl` and `inferno`:
+    ///
+    /// ```text
+    /// main;sub_2A0;sub_31C 1520
+    /// ```
+    pub fn write_folded<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        for (calls, cycles) in &self.stacks {
+            write!(w, "main")?;
+            for address in calls {
+                write!(w, ";sub_{:03X}", address)?;
+            }
+
+            writeln!(w, " {}", cycles)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a report of the `top` hottest addresses, subroutines and loops. Loops are
+    /// annotated with the disassembly of `memory` and per instruction counts.
+    pub fn write_report<W: io::Write>(
+        &self,
+        memory: &[u8],
+        top: usize,
+        w: &mut W,
+    ) -> io::Result<()> {
+        let percent = |cycles: u64| 100.0 * cycles as f64 / self.cycles.max(1) as f64;
+        let disassemble = |address: Addr| {
+            let bytes = memory.get(address as usize..address as usize + 2);
+            match bytes.and_then(Opcode::decode) {
+                Some(opcode) => opcode.to_string(),
+                None => String::from("--"),
+            }
+        };
+
+        writeln!(w, "{} cycles", self.cycles)?;
+
+        writeln!(w, "\nhottest instructions:")?;
+        for (address, count) in self.hotspots().into_iter().take(top) {
+            writeln!(
+                w,
+                "  {:03X}  {:>10}  {:5.1}%  {}",
+                address,
+                count,
+                percent(count),
+                disassemble(address)
+            )?;
+        }
+
+        if !self.subroutines.is_empty() {
+            let mut subroutines: Vec<_> = self.subroutines.iter().collect();
+            subroutines.sort_by_key(|(&address, s)| (std::cmp::Reverse(s.total_cycles), address));
+
+            writeln!(w, "\nsubroutines:       calls        self       total")?;
+            for (address, subroutine) in subroutines.into_iter().take(top) {
+                writeln!(
+                    w,
+                    "  sub_{:03X}  {:>10}  {:>10}  {:>10}  {:5.1}%",
+                    address,
+                    subroutine.calls,
+                    subroutine.self_cycles,
+                    subroutine.total_cycles,
+                    percent(subroutine.total_cycles)
+                )?;
+            }
+        }
+
+        for l in self.loops().into_iter().take(top) {
+            writeln!(
+                w,
+                "\nloop {:03X}-{:03X}: {} iterations, {} cycles ({:.1}%)",
+                l.start,
+                l.end,
+                l.iterations,
+                l.cycles,
+                percent(l.cycles)
+            )?;
+
+            for address in (l.start..=l.end).step_by(2) {
+                let count = self.counts.get(&address).copied().unwrap_or(0);
+                writeln!(
+                    w,
+                    "  {:03X}  {:>10}  {}",
+                    address,
+                    count,
+                    disassemble(address)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 200: LD V0, 0x03
+    // 202: CALL 0x20A
+    // 204: ADD V0, 0xFF
+    // 206: SE V0, 0x00
+    // 208: JP 0x202
+    // 20A: RET
+    const PROGRAM: [u8; 12] = [
+        0x60, 0x03, 0x22, 0x0A, 0x70, 0xFF, 0x30, 0x00, 0x12, 0x02, 0x00, 0xEE,
+    ];
+
+    fn profile(cycles: u64) -> Profiler {
+        let mut emulator = Emulator::new();
+        emulator.load(&PROGRAM).unwrap();
+
+        let mut profiler = Profiler::new();
+        for _ in 0..cycles {
+            profiler.step(&mut emulator).unwrap();
+        }
+
+        profiler
+    }
+
+    #[test]
+    fn counts_and_subroutines() {
+        // Three iterations of CALL, RET, ADD, SE and JP, except that the last SE skips.
+        let profiler = profile(1 + 3 * 5 - 1);
+        assert_eq!(profiler.cycles(), 15);
+        assert_eq!(profiler.counts()[&0x202], 3);
+        assert_eq!(profiler.counts().get(&0x208), Some(&2));
+        assert_eq!(profiler.hotspots()[0], (0x202, 3));
+
+        let subroutine = profiler.subroutines()[&0x20A];
+        assert_eq!(subroutine.calls, 3);
+        assert_eq!(subroutine.self_cycles, 3);
+        assert_eq!(subroutine.total_cycles, 3);
+
+        let loops = profiler.loops();
+        assert_eq!(loops.len(), 1);
+        assert_eq!((loops[0].start, loops[0].end), (0x202, 0x208));
+        assert_eq!(loops[0].iterations, 2);
+    }
+
+    #[test]
+    fn folded_stacks() {
+        let profiler = profile(7);
+        let mut out = Vec::new();
+        profiler.write_folded(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "main 6\nmain;sub_20A 1\n");
+    }
+}
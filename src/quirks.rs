@@ -0,0 +1,191 @@
+use core::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+/// [Quirks] describes the behavioural differences between the various Chip-8
+/// interpreters. Programs written for one interpreter often rely on its particular
+/// behaviour and misbehave when run with a different set of quirks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    /// `OR`, `AND` and `XOR` reset VF to zero.
+    pub vf_reset: bool,
+
+    /// `LD [I], Vx` and `LD Vx, [I]` leave I pointing past the last accessed byte.
+    pub memory_increments_i: bool,
+
+    /// `DRW` waits for the next frame before continuing execution.
+    pub display_wait: bool,
+
+    /// Sprites are clipped at the edges of the screen instead of wrapping around.
+    pub clip_sprites: bool,
+
+    /// `SHR` and `SHL` shift Vy and store the result in Vx instead of shifting Vx in
+    /// place.
+    pub shift_uses_vy: bool,
+
+    /// `JP V0, nnn` jumps to `nnn + Vx`, where `x` is the high nibble of `nnn`.
+    pub jump_uses_vx: bool,
+}
+
+impl Quirks {
+    /// Names of the individual quirks in the order used by [Quirks::flags].
+    pub const NAMES: [&'static str; 6] = [
+        "vf_reset",
+        "memory_increments_i",
+        "display_wait",
+        "clip_sprites",
+        "shift_uses_vy",
+        "jump_uses_vx",
+    ];
+
+    /// Returns the individual quirks as an array of flags.
+    pub fn flags(&self) -> [bool; 6] {
+        [
+            self.vf_reset,
+            self.memory_increments_i,
+            self.display_wait,
+            self.clip_sprites,
+            self.shift_uses_vy,
+            self.jump_uses_vx,
+        ]
+    }
+
+    /// Constructs a set of quirks from an array of flags as returned by [Quirks::flags].
+    pub fn from_flags(flags: [bool; 6]) -> Self {
+        Quirks {
+            vf_reset: flags[0],
+            memory_increments_i: flags[1],
+            display_wait: flags[2],
+            clip_sprites: flags[3],
+            shift_uses_vy: flags[4],
+            jump_uses_vx: flags[5],
+        }
+    }
+}
+
+impl Default for Quirks {
+    /// The quirks of the original COSMAC VIP interpreter.
+    fn default() -> Self {
+        Platform::Vip.quirks()
+    }
+}
+
+/// [Platform] enumerates the interpreters for which quirk presets are provided.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Platform {
+    /// The original Chip-8 interpreter on the COSMAC VIP.
+    Vip,
+
+    /// SUPER-CHIP 1.1 on the HP 48 calculators.
+    Schip,
+
+    /// The XO-CHIP extension as implemented by Octo.
+    XoChip,
+}
+
+impl Platform {
+    /// Every platform with a quirks preset.
+    pub const ALL: [Platform; 3] = [Platform::Vip, Platform::Schip, Platform::XoChip];
+
+    /// Returns the platform whose quirks preset is `quirks`, if any.
+    pub fn from_quirks(quirks: Quirks) -> Option<Platform> {
+        Platform::ALL
+            .into_iter()
+            .find(|platform| platform.quirks() == quirks)
+    }
+
+    /// Returns the number of nested subroutine calls supported by this platform.
+    pub fn stack_depth(&self) -> usize {
+        match self {
+            Platform::Vip => 12,
+            Platform::Schip | Platform::XoChip => 16,
+        }
+    }
+
+    /// Returns the quirks preset for this platform.
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Platform::Vip => Quirks {
+                vf_reset: true,
+                memory_increments_i: true,
+                display_wait: true,
+                clip_sprites: true,
+                shift_uses_vy: true,
+                jump_uses_vx: false,
+            },
+
+            Platform::Schip => Quirks {
+                vf_reset: false,
+                memory_increments_i: false,
+                display_wait: false,
+                clip_sprites: true,
+                shift_uses_vy: false,
+                jump_uses_vx: true,
+            },
+
+            Platform::XoChip => Quirks {
+                vf_reset: false,
+                memory_increments_i: true,
+                display_wait: false,
+                clip_sprites: false,
+                shift_uses_vy: true,
+                jump_uses_vx: false,
+            },
+        }
+    }
+}
+
+impl Display for Platform {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Platform::Vip => write!(f, "vip"),
+            Platform::Schip => write!(f, "schip"),
+            Platform::XoChip => write!(f, "xochip"),
+        }
+    }
+}
+
+impl FromStr for Platform {
+    type Err = ParsePlatformError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const ALIASES: [(&str, Platform); 7] = [
+            ("vip", Platform::Vip),
+            ("chip8", Platform::Vip),
+            ("chip-8", Platform::Vip),
+            ("schip", Platform::Schip),
+            ("superchip", Platform::Schip),
+            ("xochip", Platform::XoChip),
+            ("xo-chip", Platform::XoChip),
+        ];
+
+        ALIASES
+            .iter()
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(s))
+            .map(|(_, platform)| *platform)
+            .ok_or(ParsePlatformError)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de> serde::Deserialize<'de> for Platform {
+    /// Deserializes a platform from any of the names accepted by [Platform::from_str].
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// [ParsePlatformError] is returned when parsing an unknown [Platform] name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParsePlatformError;
+
+impl Display for ParsePlatformError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown platform, expected vip, schip or xochip")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParsePlatformError {}
@@ -0,0 +1,476 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    fs,
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    checksum::crc32,
+    emulation::{Emulator, Keypad, DEFAULT_TICK_RATE},
+    framebuffer::Framebuffer,
+    image::{read_image, ImageFormat, Palette, Screenshot},
+    quirks::Platform,
+};
+
+/// [Manifest] lists the ROMs checked by `chip8 test`. Manifests are written in TOML with
+/// one `[[test]]` table per test case, or in JSON when the file name ends in `.json`
+/// with the test cases in a `test` array. Paths are relative to the manifest:
+///
+/// ```toml
+/// [[test]]
+/// name = "ibm-logo"
+/// rom = "roms/ibm.ch8"
+/// frames = 60
+/// hash = "8f2c6a0e"
+///
+/// [[test]]
+/// name = "keypad"
+/// rom = "roms/keypad.ch8"
+/// platform = "schip"
+/// tick_rate = 20
+/// cycles = 3000
+/// image = "expected/keypad.ppm"
+/// inputs = [
+///     { frame = 10, keys = [5] },
+///     { frame = 14, keys = [] },
+/// ]
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(rename = "test", default)]
+    pub tests: Vec<TestCase>,
+}
+
+/// [TestCase] runs a ROM headlessly for a number of frames or instructions and compares
+/// the final display against either a framebuffer hash or a reference PNG or PPM image.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestCase {
+    pub name: String,
+    pub rom: PathBuf,
+
+    /// Platform whose quirks to emulate: vip, schip or xochip.
+    #[serde(default = "default_platform")]
+    pub platform: Platform,
+
+    #[serde(default = "default_tick_rate")]
+    pub tick_rate: u32,
+
+    #[serde(default)]
+    pub seed: u32,
+
+    /// Number of 60 Hz frames to run before checking the display.
+    pub frames: Option<u64>,
+
+    /// Number of instructions to run before checking the display, instead of `frames`.
+    pub cycles: Option<u64>,
+
+    /// Keys held down from a given frame onwards, in increasing frame order.
+    #[serde(default)]
+    pub inputs: Vec<Input>,
+
+    /// Expected CRC-32 of the framebuffer as eight hexadecimal digits.
+    pub hash: Option<String>,
+
+    /// Reference image of the expected display, as written by `chip8 run --screenshot`
+    /// in PNG or PPM format.
+    pub image: Option<PathBuf>,
+}
+
+/// [Input] sets the keys which are held down from `frame` until the next input.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Input {
+    pub frame: u64,
+    pub keys: Vec<u8>,
+}
+
+/// [TestResult] is the outcome of running a single [TestCase].
+#[derive(Debug)]
+pub enum TestResult {
+    Pass,
+
+    /// The final display did not match. `expected` holds the reference image if the
+    /// test case has one.
+    Mismatch {
+        actual: Box<Framebuffer>,
+        expected: Option<Box<Framebuffer>>,
+    },
+
+    /// The test could not be run to completion.
+    Error(String),
+}
+
+impl Manifest {
+    /// Reads a manifest from a TOML file, or a JSON file if its extension is `json`.
+    pub fn read(path: &Path) -> Result<Self, ManifestError> {
+        let content = fs::read_to_string(path)?;
+        let json = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+
+        if json {
+            serde_json::from_str(&content).map_err(|err| ManifestError::Parse(err.to_string()))
+        } else {
+            toml::from_str(&content).map_err(|err| ManifestError::Parse(err.to_string()))
+        }
+    }
+
+    /// Runs every test case with paths relative to `base`, writing a line per test and
+    /// a final tally to `w`. Reference images are read with the palette of `screenshot`.
+    /// The display of each mismatching test is written to `diff_dir` as a PNG image,
+    /// highlighting the differences if there is a reference image. Returns the number of
+    /// tests which failed.
+    pub fn run<W: Write>(
+        &self,
+        base: &Path,
+        diff_dir: &Path,
+        screenshot: &Screenshot,
+        w: &mut W,
+    ) -> io::Result<usize> {
+        let mut failed = 0;
+        for test in &self.tests {
+            match test.run(base, &screenshot.palette()) {
+                TestResult::Pass => writeln!(w, "PASS {}", test.name)?,
+                TestResult::Mismatch { actual, expected } => {
+                    failed += 1;
+                    let diff_path = diff_dir.join(format!("{}.diff.png", file_name(&test.name)));
+                    let mut file = BufWriter::new(fs::File::create(&diff_path)?);
+                    match expected {
+                        Some(expected) => {
+                            screenshot.write_diff_png(&expected, &actual, &mut file)?
+                        }
+                        None => screenshot.write_png(&actual, &mut file)?,
+                    }
+
+                    file.flush()?;
+                    writeln!(
+                        w,
+                        "FAIL {}: display mismatch, actual hash is {:08x}, see {}",
+                        test.name,
+                        framebuffer_hash(&actual),
+                        diff_path.display()
+                    )?;
+                }
+
+                TestResult::Error(message) => {
+                    failed += 1;
+                    writeln!(w, "FAIL {}: {}", test.name, message)?;
+                }
+            }
+        }
+
+        writeln!(w, "{} passed, {} failed", self.tests.len() - failed, failed)?;
+        Ok(failed)
+    }
+}
+
+impl TestCase {
+    /// Runs the test case, resolving paths relative to `base`. Pixels of the reference
+    /// image are lit if they are closer to the foreground of `palette` than to the
+    /// background.
+    pub fn run(&self, base: &Path, palette: &Palette) -> TestResult {
+        match self.execute(base, palette) {
+            Ok(result) => result,
+            Err(message) => TestResult::Error(message),
+        }
+    }
+
+    fn execute(&self, base: &Path, palette: &Palette) -> Result<TestResult, String> {
+        let rom_path = base.join(&self.rom);
+        let rom = fs::read(&rom_path).map_err(|err| format!("{}: {}", rom_path.display(), err))?;
+
+        let mut inputs = Vec::with_capacity(self.inputs.len());
+        for input in &self.inputs {
+            if inputs
+                .last()
+                .is_some_and(|&(frame, _)| frame >= input.frame)
+            {
+                return Err(format!(
+                    "inputs are not in increasing frame order at frame {}",
+                    input.frame
+                ));
+            }
+
+            let mut keypad = Keypad::default();
+            for &key in &input.keys {
+                if key > 0xF {
+                    return Err(format!("invalid key {} at frame {}", key, input.frame));
+                }
+
+                keypad.set(key, true);
+            }
+
+            inputs.push((input.frame, keypad));
+        }
+
+        let done: Box<dyn Fn(&Emulator) -> bool> = match (self.frames, self.cycles) {
+            (Some(frames), None) => Box::new(move |emulator| emulator.frames() >= frames),
+            (None, Some(cycles)) => Box::new(move |emulator| emulator.cycles() >= cycles),
+            _ => return Err(String::from("expected exactly one of frames or cycles")),
+        };
+
+        let mut emulator = Emulator::new()
+            .with_quirks(self.platform.quirks())
+            .with_tick_rate(self.tick_rate)
+            .with_stack_depth(self.platform.stack_depth())
+            .with_seed(self.seed);
+        emulator.load(&rom).map_err(|err| err.to_string())?;
+
+        // Keys change at the start of a frame, before its first instruction.
+        let mut inputs = inputs.into_iter().peekable();
+        while !done(&emulator) {
+            while let Some((_, keypad)) = inputs.next_if(|&(frame, _)| frame <= emulator.frames()) {
+                emulator.set_keypad(keypad);
+            }
+
+            emulator.step().map_err(|err| err.to_string())?;
+        }
+
+        let actual = Box::new(*emulator.framebuffer());
+        let expected = match &self.image {
+            Some(path) => {
+                let path = base.join(path);
+                let format = ImageFormat::from_extension(&path).ok_or_else(|| {
+                    format!("{}: reference images must be .png or .ppm", path.display())
+                })?;
+                let file =
+                    fs::File::open(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+                let image = read_image(&mut BufReader::new(file), format, palette)
+                    .map_err(|err| format!("{}: {}", path.display(), err))?;
+                Some(Box::new(image))
+            }
+
+            None => None,
+        };
+
+        let hash = match &self.hash {
+            Some(hash) => Some(
+                u32::from_str_radix(hash, 16).map_err(|_| format!("invalid hash '{}'", hash))?,
+            ),
+            None => None,
+        };
+
+        if expected.is_none() && hash.is_none() {
+            return Err(format!(
+                "no expected hash or image, the actual hash is {:08x}",
+                framebuffer_hash(&actual)
+            ));
+        }
+
+        let matches = expected.as_ref().is_none_or(|expected| *expected == actual)
+            && hash.is_none_or(|hash| hash == framebuffer_hash(&actual));
+
+        if matches {
+            Ok(TestResult::Pass)
+        } else {
+            Ok(TestResult::Mismatch { actual, expected })
+        }
+    }
+}
+
+/// Returns the CRC-32 of a framebuffer's rows, used to compare displays compactly.
+pub fn framebuffer_hash(framebuffer: &Framebuffer) -> u32 {
+    let bytes: Vec<u8> = framebuffer
+        .rows()
+        .iter()
+        .flat_map(|row| row.to_be_bytes())
+        .collect();
+
+    crc32(&bytes)
+}
+
+/// Turns a test name into a file name by replacing anything but letters, digits and
+/// dashes with underscores.
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn default_platform() -> Platform {
+    Platform::Vip
+}
+
+fn default_tick_rate() -> u32 {
+    DEFAULT_TICK_RATE
+}
+
+#[derive(Debug)]
+pub enum ManifestError {
+    Io(io::Error),
+    Parse(String),
+}
+
+impl Display for ManifestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestError::Io(err) => write!(f, "{}", err),
+            ManifestError::Parse(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+impl From<io::Error> for ManifestError {
+    fn from(err: io::Error) -> Self {
+        ManifestError::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::Color;
+
+    #[test]
+    fn parse_manifest() {
+        let manifest: Manifest = toml::from_str(
+            r#"
+            [[test]]
+            name = "logo"
+            rom = "logo.ch8"
+            frames = 60
+            hash = "0123abcd"
+
+            [[test]]
+            name = "keys"
+            rom = "keys.ch8"
+            platform = "schip"
+            frames = 10
+            image = "keys.ppm"
+            inputs = [{ frame = 2, keys = [1, 15] }]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(manifest.tests.len(), 2);
+        assert_eq!(manifest.tests[0].platform, Platform::Vip);
+        assert_eq!(manifest.tests[1].platform, Platform::Schip);
+        assert_eq!(manifest.tests[0].tick_rate, DEFAULT_TICK_RATE);
+        assert_eq!(manifest.tests[1].inputs[0].keys, [1, 15]);
+        assert!(toml::from_str::<Manifest>("[[test]]\nname = 'x'\nbogus = 1").is_err());
+
+        let typo = "[[test]]\nname = 'x'\nrom = 'x.ch8'\nplatform = 'schp'";
+        let err = toml::from_str::<Manifest>(typo).unwrap_err();
+        assert!(err.to_string().contains("unknown platform"));
+
+        let manifest: Manifest = serde_json::from_str(
+            r#"{ "test": [{ "name": "logo", "rom": "logo.ch8", "cycles": 100, "hash": "0" }] }"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.tests[0].cycles, Some(100));
+        assert_eq!(manifest.tests[0].frames, None);
+    }
+
+    // 200: LD I, 0x206
+    // 202: DRW V0, V0, 1
+    // 204: JP 0x204
+    // 206: sprite
+    const PROGRAM: [u8; 7] = [0xA2, 0x06, 0xD0, 0x01, 0x12, 0x04, 0xFF];
+
+    /// Writes [PROGRAM] to a directory of its own, returning the directory.
+    fn rom_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("chip8-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("test.ch8"), PROGRAM).unwrap();
+        dir
+    }
+
+    fn test_case(toml: &str) -> TestCase {
+        let manifest: Manifest =
+            toml::from_str(&format!("[[test]]\nname = 't'\nrom = 'test.ch8'\n{}", toml)).unwrap();
+        manifest.tests.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn execute_passes() {
+        let dir = rom_dir("pass");
+        let mut expected = Framebuffer::default();
+        expected.draw_sprite(0, 0, &[0xFF], false);
+        let hash = framebuffer_hash(&expected);
+
+        let test = test_case(&format!("frames = 2\nhash = '{:08x}'", hash));
+        assert!(matches!(
+            test.execute(&dir, &Palette::default()),
+            Ok(TestResult::Pass)
+        ));
+
+        // Only `LD I` has run after one cycle so the display is still blank.
+        let blank = framebuffer_hash(&Framebuffer::default());
+        let test = test_case(&format!("cycles = 1\nhash = '{:08x}'", blank));
+        assert!(matches!(
+            test.execute(&dir, &Palette::default()),
+            Ok(TestResult::Pass)
+        ));
+
+        // References exported with a dark-on-light palette are read with that palette.
+        let palette = Palette {
+            foreground: Color([0x00, 0x00, 0x00]),
+            background: Color([0xFF, 0xFF, 0xFF]),
+        };
+        let mut image = Vec::new();
+        let screenshot = Screenshot::new().with_scale(2).with_palette(palette);
+        screenshot.write_ppm(&expected, &mut image).unwrap();
+        fs::write(dir.join("expected.ppm"), image).unwrap();
+
+        let test = test_case("frames = 2\nimage = 'expected.ppm'");
+        assert!(matches!(test.execute(&dir, &palette), Ok(TestResult::Pass)));
+
+        let mut image = Vec::new();
+        screenshot.write_png(&expected, &mut image).unwrap();
+        fs::write(dir.join("expected.png"), image).unwrap();
+
+        let test = test_case("frames = 2\nimage = 'expected.png'");
+        assert!(matches!(test.execute(&dir, &palette), Ok(TestResult::Pass)));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn execute_mismatches() {
+        let dir = rom_dir("mismatch");
+        let test = test_case("frames = 2\nhash = '00000000'");
+        match test.execute(&dir, &Palette::default()) {
+            Ok(TestResult::Mismatch { actual, expected }) => {
+                assert!(actual.pixel(7, 0) && !actual.pixel(8, 0));
+                assert!(expected.is_none());
+            }
+
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        let test = test_case(
+            "frames = 2\nhash = '0'\ninputs = [{ frame = 2, keys = [] }, { frame = 1, keys = [] }]",
+        );
+        assert!(test
+            .execute(&dir, &Palette::default())
+            .unwrap_err()
+            .contains("increasing frame order"));
+
+        let test = test_case("frames = 2\nimage = 'expected.bmp'");
+        assert!(test
+            .execute(&dir, &Palette::default())
+            .unwrap_err()
+            .contains("must be .png or .ppm"));
+
+        let test = test_case("frames = 2\ncycles = 5\nhash = '0'");
+        assert!(test
+            .execute(&dir, &Palette::default())
+            .unwrap_err()
+            .contains("frames or cycles"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,277 @@
+use std::{collections::BTreeMap, io};
+
+use crate::{
+    data::Addr,
+    emulation::START_ADDRESS,
+    image::{write_indexed_png, Palette},
+    lint::Linter,
+    quirks::Platform,
+};
+
+/// Width and height in pixels of the cells of a sprite sheet, fitting the largest sprite.
+const CELL_SIZE: usize = 16;
+
+/// Number of sprites in each row of a sprite sheet.
+const SHEET_COLUMNS: usize = 8;
+
+/// [Sprite] is a bitmap a program draws with `DRW`, found by following `LD I` to the
+/// instructions drawing from it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sprite {
+    pub address: Addr,
+
+    /// Width in pixels: 8, or 16 for the sprites drawn by `DRW Vx, Vy, 0` on SUPER-CHIP
+    /// and XO-CHIP.
+    pub width: usize,
+    pub height: usize,
+
+    /// Rows of the sprite, each `width / 8` bytes long.
+    pub data: Vec<u8>,
+
+    /// Whether the sprite runs past the end of the program, leaving out the missing rows.
+    pub clamped: bool,
+
+    /// Addresses of the `DRW` instructions which draw the sprite.
+    pub draws: Vec<Addr>,
+}
+
+impl Sprite {
+    /// Returns whether the pixel at column `x` and row `y` is set.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        let bytes_per_row = self.width / 8;
+        let byte = self.data[y * bytes_per_row + x / 8];
+        byte & (0x80 >> (x % 8)) != 0
+    }
+
+    /// Renders the sprite as text, one line per row. Set pixels are drawn as `#` and
+    /// unset ones as `.`, or with full blocks when `unicode` is set.
+    pub fn render_text(&self, unicode: bool) -> String {
+        let (set, unset) = if unicode {
+            ("██", "  ")
+        } else {
+            ("#", ".")
+        };
+
+        let mut text = String::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                text.push_str(if self.pixel(x, y) { set } else { unset });
+            }
+
+            text.push('\n');
+        }
+
+        text
+    }
+
+    /// Writes the sprite as assembler `DB` directives, one row per line with its pixels
+    /// in a comment, under a label named after its address.
+    pub fn write_db<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "sprite_{:03X}:", self.address)?;
+
+        let bytes_per_row = self.width / 8;
+        for (y, row) in self.data.chunks(bytes_per_row).enumerate() {
+            let bytes: Vec<String> = row.iter().map(|b| format!("0x{:02X}", b)).collect();
+            let pixels: String = (0..self.width)
+                .map(|x| if self.pixel(x, y) { '#' } else { '.' })
+                .collect();
+            writeln!(w, "    DB   {}  ; {}", bytes.join(", "), pixels)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Finds the sprites a program draws from its own data on `platform`. Sprites drawn
+/// with different heights from the same address are reported once with the largest
+/// height, and addresses outside the program, such as the built-in font, are skipped.
+/// Sprites running past the end of the program are cut short and marked as clamped.
+pub fn find(linter: &Linter, program: &[u8], platform: Platform) -> Vec<Sprite> {
+    let mut sprites: BTreeMap<Addr, Sprite> = BTreeMap::new();
+    for draw in linter.draws(program) {
+        let (width, height) = match (draw.height, platform) {
+            // The original interpreter draws nothing for a height of zero.
+            (0, Platform::Vip) => continue,
+            (0, Platform::Schip | Platform::XoChip) => (16, 16),
+            (height, _) => (8, height as usize),
+        };
+
+        let start = match draw.sprite.checked_sub(START_ADDRESS) {
+            Some(start) if (start as usize) < program.len() => start as usize,
+            _ => continue,
+        };
+
+        let bytes_per_row = width / 8;
+        let available = (program.len() - start) / bytes_per_row;
+        let clamped = available < height;
+        let height = height.min(available);
+        if height == 0 {
+            continue;
+        }
+
+        let data = &program[start..start + bytes_per_row * height];
+        let sprite = sprites.entry(draw.sprite).or_insert_with(|| Sprite {
+            address: draw.sprite,
+            width,
+            height,
+            data: data.to_vec(),
+            clamped,
+            draws: Vec::new(),
+        });
+
+        if data.len() > sprite.data.len() || clamped && data.len() == sprite.data.len() {
+            sprite.width = width;
+            sprite.height = height;
+            sprite.data = data.to_vec();
+            sprite.clamped = clamped;
+        }
+
+        sprite.draws.push(draw.address);
+    }
+
+    sprites.into_values().collect()
+}
+
+/// Writes a heading for each sprite giving its address, size and the instructions which
+/// draw it, followed by the sprite rendered with [Sprite::render_text].
+pub fn write_text<W: io::Write>(sprites: &[Sprite], unicode: bool, w: &mut W) -> io::Result<()> {
+    for sprite in sprites {
+        let draws: Vec<String> = sprite
+            .draws
+            .iter()
+            .map(|address| format!("0x{:03X}", address))
+            .collect();
+
+        writeln!(
+            w,
+            "sprite at 0x{:03X}, {}x{}, drawn at {}",
+            sprite.address,
+            sprite.width,
+            sprite.height,
+            draws.join(", ")
+        )?;
+        if sprite.clamped {
+            writeln!(w, "warning: the sprite runs past the end of the program")?;
+        }
+
+        writeln!(w, "{}", sprite.render_text(unicode))?;
+    }
+
+    Ok(())
+}
+
+/// Writes each sprite as a block of `DB` directives with [Sprite::write_db], separated by
+/// blank lines.
+pub fn write_db_blocks<W: io::Write>(sprites: &[Sprite], w: &mut W) -> io::Result<()> {
+    for sprite in sprites {
+        sprite.write_db(w)?;
+        writeln!(w)?;
+    }
+
+    Ok(())
+}
+
+/// Writes sprites as a PNG sheet laid out in a grid of 16x16 cells separated by a pixel,
+/// with each sprite pixel scaled to `scale` image pixels.
+pub fn write_sheet_png<W: io::Write>(
+    sprites: &[Sprite],
+    scale: usize,
+    palette: Palette,
+    w: &mut W,
+) -> io::Result<()> {
+    let pitch = CELL_SIZE + 1;
+    let columns = sprites.len().clamp(1, SHEET_COLUMNS);
+    let rows = sprites.len().div_ceil(SHEET_COLUMNS).max(1);
+    let (width, height) = (columns * pitch - 1, rows * pitch - 1);
+
+    let colors = [palette.background, palette.foreground];
+    write_indexed_png(w, width * scale, height * scale, &colors, |x, y| {
+        let (x, y) = (x / scale, y / scale);
+        let index = (y / pitch) * SHEET_COLUMNS + x / pitch;
+        let (x, y) = (x % pitch, y % pitch);
+        match sprites.get(index) {
+            Some(sprite) if x < sprite.width && y < sprite.height => sprite.pixel(x, y) as u8,
+            _ => 0,
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::Color;
+
+    // 200: LD I, 0x20C
+    // 202: DRW V0, V0, 3
+    // 204: LD I, 0x20C
+    // 206: DRW V0, V0, 2
+    // 208: LD F, V0
+    // 20A: DRW V0, V0, 5
+    // 20C: sprite
+    const PROGRAM: [u8; 15] = [
+        0xA2, 0x0C, 0xD0, 0x03, 0xA2, 0x0C, 0xD0, 0x02, 0xF0, 0x29, 0xD0, 0x05, 0x3C, 0x42, 0xFF,
+    ];
+
+    #[test]
+    fn find_and_render() {
+        let sprites = find(&Linter::new(), &PROGRAM, Platform::Vip);
+        assert_eq!(sprites.len(), 1);
+
+        let sprite = &sprites[0];
+        assert_eq!((sprite.address, sprite.width, sprite.height), (0x20C, 8, 3));
+        assert_eq!(sprite.draws, [0x202, 0x206]);
+        assert_eq!(sprite.render_text(false), "..####..\n.#....#.\n########\n");
+
+        let mut out = Vec::new();
+        sprite.write_db(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "sprite_20C:\n    DB   0x3C  ; ..####..\n    DB   0x42  ; .#....#.\n    DB   0xFF  ; ########\n"
+        );
+
+        let mut png = Vec::new();
+        let palette = Palette {
+            foreground: Color([0xFF; 3]),
+            background: Color([0x00; 3]),
+        };
+        write_sheet_png(&sprites, 2, palette, &mut png).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+        assert_eq!(&png[16..24], &[0, 0, 0, 32, 0, 0, 0, 32]);
+    }
+
+    #[test]
+    fn large_and_clamped() {
+        // 200: LD I, 0x206
+        // 202: DRW V0, V0, 0
+        // 204: DRW V0, V0, 4
+        // 206: sprite, 3 bytes
+        let program = [0xA2, 0x06, 0xD0, 0x00, 0xD0, 0x04, 0x18, 0x3C, 0x7E];
+
+        let sprites = find(&Linter::new(), &program, Platform::Vip);
+        let sprite = &sprites[0];
+        assert_eq!((sprite.width, sprite.height, sprite.clamped), (8, 3, true));
+        assert_eq!(sprite.draws, [0x204]);
+
+        let mut out = Vec::new();
+        write_text(&sprites, false, &mut out).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .starts_with("sprite at 0x206, 8x3, drawn at 0x204\nwarning: "));
+
+        // The larger sprite drawn from the same address is kept.
+        let sprites = find(&Linter::new(), &program, Platform::Schip);
+        assert_eq!((sprites[0].width, sprites[0].height), (8, 3));
+        assert_eq!(sprites[0].draws, [0x202, 0x204]);
+
+        // 200: LD I, 0x204
+        // 202: DRW V0, V0, 0
+        // 204: sprite, 3 bytes, of which one 16 pixel row fits
+        let program = [0xA2, 0x04, 0xD0, 0x00, 0x18, 0x3C, 0x7E];
+        assert!(find(&Linter::new(), &program, Platform::Vip).is_empty());
+
+        let sprites = find(&Linter::new(), &program, Platform::XoChip);
+        let sprite = &sprites[0];
+        assert_eq!((sprite.width, sprite.height, sprite.clamped), (16, 1, true));
+        assert_eq!(sprite.data, [0x18, 0x3C]);
+    }
+}
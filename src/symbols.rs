@@ -0,0 +1,261 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{
+    data::Addr,
+    emulation::START_ADDRESS,
+    lint::Linter,
+    opcode::{Flow, Opcode},
+};
+
+/// [Symbols] holds what is known about the layout of a program: names of addresses,
+/// comments on instructions and the regions which hold data rather than code. The
+/// [Disassembler](crate::Disassembler) uses them to annotate its listing.
+///
+/// Symbol files are text with one declaration per line. Addresses are hexadecimal and
+/// data regions include their end address. Comments run to the end of the line. Blank
+/// lines and lines starting with `#` are ignored:
+///
+/// ```text
+/// label 200 main
+/// comment 204 wait for a key press
+/// data 2F0 2FF player_sprite
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Symbols {
+    labels: BTreeMap<Addr, String>,
+    comments: BTreeMap<Addr, String>,
+
+    /// Inclusive end address of each data region keyed by its start.
+    data: BTreeMap<Addr, Addr>,
+}
+
+impl Symbols {
+    /// Constructs an empty set of symbols.
+    pub fn new() -> Self {
+        Symbols::default()
+    }
+
+    /// Parses a symbol file.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut symbols = Symbols::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let err = |message: &str| format!("line {}: {}", i + 1, message);
+            let address = |word: Option<&str>| {
+                let word = word.ok_or_else(|| err("expected an address"))?;
+                u16::from_str_radix(word.trim_start_matches("0x"), 16)
+                    .map_err(|_| err("invalid address"))
+            };
+
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("label") => {
+                    let start = address(words.next())?;
+                    match (words.next(), words.next()) {
+                        (Some(name), None) => symbols.insert_label(start, name),
+                        _ => return Err(err("expected a single word label")),
+                    }
+                }
+
+                Some("comment") => {
+                    let start = address(words.next())?;
+                    symbols.insert_comment(start, &words.collect::<Vec<_>>().join(" "));
+                }
+
+                Some("data") => {
+                    let start = address(words.next())?;
+                    let end = address(words.next())?;
+                    if end < start {
+                        return Err(err("data region ends before it starts"));
+                    }
+
+                    symbols.insert_data(start, end);
+                    match (words.next(), words.next()) {
+                        (Some(name), None) => symbols.insert_label(start, name),
+                        (None, None) => {}
+                        _ => return Err(err("expected a single word label")),
+                    }
+                }
+
+                _ => return Err(err("expected label, comment or data")),
+            }
+        }
+
+        Ok(symbols)
+    }
+
+    /// Constructs symbols for `program` from its control flow: labels for the targets of
+    /// jumps and calls, and data regions for the bytes which are not reachable as code.
+    /// Targets are named `sub_`, `table_` or `label_` followed by their address.
+    pub fn generate(program: &[u8]) -> Self {
+        let reachability = Linter::new().reachability(program);
+        let end = START_ADDRESS as usize + program.len();
+
+        let mut symbols = Symbols::new();
+        for &address in &reachability.instructions {
+            let offset = (address - START_ADDRESS) as usize;
+            let (prefix, target) = match Opcode::decode(&program[offset..offset + 2]) {
+                Some(opcode) => match opcode.flow() {
+                    Flow::Call(target) => ("sub", target),
+                    Flow::Indirect(target) => ("table", target),
+                    Flow::Jump(target) => ("label", target),
+                    _ => continue,
+                },
+                None => continue,
+            };
+
+            let in_program = (START_ADDRESS as usize..end).contains(&(target as usize));
+            if in_program && (prefix == "sub" || symbols.label(target).is_none()) {
+                symbols.insert_label(target, &format!("{}_{:03X}", prefix, target));
+            }
+        }
+
+        let code: BTreeSet<Addr> = reachability
+            .instructions
+            .iter()
+            .chain(&reachability.undecodable)
+            .flat_map(|&address| [address, address.wrapping_add(1)])
+            .collect();
+        let mut start = None;
+        for address in START_ADDRESS as usize..=end {
+            let is_data = address < end && !code.contains(&(address as Addr));
+            match (start, is_data) {
+                (None, true) => start = Some(address as Addr),
+                (Some(first), false) => {
+                    symbols.insert_data(first, address as Addr - 1);
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+
+        symbols
+    }
+
+    /// Adds the declarations of `other`, replacing any labels and comments of the same
+    /// addresses.
+    pub fn extend(&mut self, other: Symbols) {
+        self.labels.extend(other.labels);
+        self.comments.extend(other.comments);
+        for (start, end) in other.data {
+            let end = self.data.get(&start).map_or(end, |&e| e.max(end));
+            self.data.insert(start, end);
+        }
+    }
+
+    /// Names an address.
+    pub fn insert_label(&mut self, address: Addr, name: &str) {
+        self.labels.insert(address, String::from(name));
+    }
+
+    /// Attaches a comment to the instruction at an address.
+    pub fn insert_comment(&mut self, address: Addr, comment: &str) {
+        self.comments.insert(address, String::from(comment));
+    }
+
+    /// Declares the bytes from `start` to `end` inclusive as data.
+    pub fn insert_data(&mut self, start: Addr, end: Addr) {
+        self.data.insert(start, end);
+    }
+
+    /// Returns the name of an address.
+    pub fn label(&self, address: Addr) -> Option<&str> {
+        self.labels.get(&address).map(String::as_str)
+    }
+
+    /// Returns the comment on the instruction at an address.
+    pub fn comment(&self, address: Addr) -> Option<&str> {
+        self.comments.get(&address).map(String::as_str)
+    }
+
+    /// Returns the inclusive end of the data region containing an address, if any.
+    pub fn data_end(&self, address: Addr) -> Option<Addr> {
+        // Regions may overlap, so an earlier region can extend past a later one.
+        self.data
+            .range(..=address)
+            .map(|(_, &end)| end)
+            .filter(|&end| address <= end)
+            .max()
+    }
+}
+
+impl Display for Symbols {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (address, name) in &self.labels {
+            if !self.data.contains_key(address) {
+                writeln!(f, "label {:03X} {}", address, name)?;
+            }
+        }
+
+        for (start, end) in &self.data {
+            write!(f, "data {:03X} {:03X}", start, end)?;
+            match self.labels.get(start) {
+                Some(name) => writeln!(f, " {}", name)?,
+                None => writeln!(f)?,
+            }
+        }
+
+        for (address, comment) in &self.comments {
+            writeln!(f, "comment {:03X} {}", address, comment)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_and_write() {
+        let text = "\
+# symbols for example.ch8
+label  200   main
+comment 204   wait for a key press
+data 0x2F0 2FF player_sprite
+data 300 301
+";
+        let symbols = Symbols::parse(text).unwrap();
+        assert_eq!(symbols.label(0x200), Some("main"));
+        assert_eq!(symbols.label(0x2F0), Some("player_sprite"));
+        assert_eq!(symbols.comment(0x204), Some("wait for a key press"));
+        assert_eq!(symbols.data_end(0x2F0), Some(0x2FF));
+        assert_eq!(symbols.data_end(0x2F8), Some(0x2FF));
+        assert_eq!(symbols.data_end(0x2EE), None);
+        assert_eq!(symbols.data_end(0x302), None);
+
+        assert_eq!(Symbols::parse(&symbols.to_string()), Ok(symbols));
+        assert!(Symbols::parse("label 200").is_err());
+        assert!(Symbols::parse("data 2FF 2F0").is_err());
+        assert!(Symbols::parse("name 200 main").is_err());
+    }
+
+    #[test]
+    fn overlapping_data() {
+        let symbols = Symbols::parse("data 300 30F\ndata 304 305").unwrap();
+        assert_eq!(symbols.data_end(0x304), Some(0x30F));
+        assert_eq!(symbols.data_end(0x308), Some(0x30F));
+        assert_eq!(symbols.data_end(0x310), None);
+    }
+
+    #[test]
+    fn generate() {
+        // CALL 0x208; JP V0, 0x20A; (data); RET; (data)
+        let program = [
+            0x22, 0x08, 0xB2, 0x0A, 0xAB, 0xCD, 0xEF, 0x01, 0x00, 0xEE, 0xFF,
+        ];
+        let symbols = Symbols::generate(&program);
+        assert_eq!(
+            symbols.to_string(),
+            "label 208 sub_208\ndata 204 207\ndata 20A 20A table_20A\n"
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Read, Write},
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use chip8::{
+    emulation::Keypad,
+    framebuffer::{Framebuffer, HEIGHT, WIDTH},
+};
+
+/// Number of frames a key is held down for after a key press is read. Terminals do not
+/// report key releases so held keys are kept alive by the terminal's key repeat.
+const HOLD_FRAMES: u8 = 8;
+
+/// Keyboard layout mapping the left-hand side of a QWERTY keyboard onto the keypad:
+///
+/// ```text
+/// 1 2 3 4      1 2 3 C
+/// q w e r  ->  4 5 6 D
+/// a s d f      7 8 9 E
+/// z x c v      A 0 B F
+/// ```
+const KEY_MAP: [(u8, u8); 16] = [
+    (b'1', 0x1),
+    (b'2', 0x2),
+    (b'3', 0x3),
+    (b'4', 0xC),
+    (b'q', 0x4),
+    (b'w', 0x5),
+    (b'e', 0x6),
+    (b'r', 0xD),
+    (b'a', 0x7),
+    (b's', 0x8),
+    (b'd', 0x9),
+    (b'f', 0xE),
+    (b'z', 0xA),
+    (b'x', 0x0),
+    (b'c', 0xB),
+    (b'v', 0xF),
+];
+
+/// Input sequences of the keys which can be bound to the named inputs of the ROM
+/// database: the arrow keys, space and enter.
+const NAMED_INPUTS: [(&str, &[u8]); 7] = [
+    ("up", b"\x1B[A"),
+    ("down", b"\x1B[B"),
+    ("right", b"\x1B[C"),
+    ("left", b"\x1B[D"),
+    ("a", b" "),
+    ("b", b"\n"),
+    ("b", b"\r"),
+];
+
+/// Byte read when the escape key is pressed.
+const ESCAPE: u8 = 0x1B;
+
+/// Byte read when Ctrl-C is pressed while signals are disabled.
+const INTERRUPT: u8 = 0x03;
+
+/// [Terminal] is a display and keyboard frontend for the emulator running in a text
+/// terminal. The terminal is switched into raw mode for as long as this value lives.
+pub struct Terminal {
+    saved_mode: String,
+    input: Receiver<u8>,
+
+    /// Input sequences bound to keys with [Terminal::with_keys].
+    bindings: Vec<(&'static [u8], u8)>,
+    held: [u8; 16],
+    drawn: Option<Framebuffer>,
+}
+
+impl Terminal {
+    /// Switches the terminal into raw mode and starts listening for key presses.
+    pub fn open() -> io::Result<Self> {
+        let saved_mode = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "-isig", "min", "1"])?;
+
+        let (sender, input) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buffer = [0; 32];
+            let mut stdin = io::stdin();
+            while let Ok(len @ 1..) = stdin.read(&mut buffer) {
+                for &byte in &buffer[..len] {
+                    if sender.send(byte).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        // Clear the screen and hide the cursor.
+        print!("\x1B[2J\x1B[?25l");
+        Ok(Terminal {
+            saved_mode: saved_mode.trim().to_string(),
+            input,
+            bindings: Vec::new(),
+            held: [0; 16],
+            drawn: None,
+        })
+    }
+
+    /// Binds the arrow keys, space and enter to the keypad keys of the named inputs of a
+    /// ROM, as given by the ROM database, in addition to the usual layout.
+    pub fn with_keys(mut self, keys: &BTreeMap<String, u8>) -> Self {
+        self.bindings = NAMED_INPUTS
+            .iter()
+            .filter_map(|&(name, input)| keys.get(name).map(|&key| (input, key & 0xF)))
+            .collect();
+
+        self
+    }
+
+    /// Updates `keypad` with the keys pressed since the last call. Returns false if the
+    /// user asked to quit by pressing escape or Ctrl-C.
+    pub fn poll(&mut self, keypad: &mut Keypad) -> bool {
+        for hold in self.held.iter_mut() {
+            *hold = hold.saturating_sub(1);
+        }
+
+        let bytes: Vec<u8> = self.input.try_iter().collect();
+        let mut rest = bytes.as_slice();
+        while let Some(&byte) = rest.first() {
+            if let Some(&(input, key)) = self.bindings.iter().find(|(i, _)| rest.starts_with(i)) {
+                self.held[key as usize] = HOLD_FRAMES;
+                rest = &rest[input.len()..];
+                continue;
+            }
+
+            // Other escape sequences, such as unbound arrow keys, are ignored.
+            if rest.starts_with(&[ESCAPE, b'[']) {
+                rest = &rest[rest.len().min(3)..];
+                continue;
+            }
+
+            if byte == ESCAPE || byte == INTERRUPT {
+                return false;
+            }
+
+            let byte = byte.to_ascii_lowercase();
+            if let Some(&(_, key)) = KEY_MAP.iter().find(|(c, _)| *c == byte) {
+                self.held[key as usize] = HOLD_FRAMES;
+            }
+
+            rest = &rest[1..];
+        }
+
+        for (key, hold) in self.held.iter().enumerate() {
+            keypad.set(key as u8, *hold > 0);
+        }
+
+        true
+    }
+
+    /// Draws the framebuffer if it changed since the last call.
+    pub fn draw(&mut self, framebuffer: &Framebuffer) -> io::Result<()> {
+        if self.drawn.as_ref() == Some(framebuffer) {
+            return Ok(());
+        }
+
+        let mut stdout = io::stdout().lock();
+        write!(
+            stdout,
+            "\x1B[H{}",
+            render_text(framebuffer).replace('\n', "\r\n")
+        )?;
+        stdout.flush()?;
+        self.drawn = Some(*framebuffer);
+        Ok(())
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        print!("\x1B[?25h\r\n");
+        let _ = io::stdout().flush();
+        let _ = stty(&[&self.saved_mode]);
+    }
+}
+
+/// Renders a framebuffer as text using Unicode half blocks so that each character
+/// covers two vertically adjacent pixels.
+pub fn render_text(framebuffer: &Framebuffer) -> String {
+    let mut text = String::with_capacity((WIDTH * 3 + 1) * HEIGHT / 2);
+    for y in (0..HEIGHT).step_by(2) {
+        for x in 0..WIDTH {
+            let c = match (framebuffer.pixel(x, y), framebuffer.pixel(x, y + 1)) {
+                (true, true) => '█',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (false, false) => ' ',
+            };
+
+            text.push(c);
+        }
+
+        text.push('\n');
+    }
+
+    text
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()?;
+
+    if !output.status.success() {
+        return Err(io::Error::other("stdin is not a terminal"));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
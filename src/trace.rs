@@ -0,0 +1,584 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    io::{self, BufRead},
+};
+
+use crate::{data::Addr, emulation::Emulator, opcode::Opcode};
+
+/// [TraceRecord] is a single line of an execution trace: the state of the machine right
+/// before an instruction is executed along with the memory writes made by it.
+///
+/// Records are written as whitespace separated `KEY=VALUE` pairs with hexadecimal
+/// values, for example:
+///
+/// ```text
+/// CYC=1a PC=0204 OP=8126 V0=00 ... VF=01 I=0300 SP=1 DT=00 ST=00 [0300]=7f
+/// ```
+///
+/// Only `PC` and `OP` are required, so logs produced by other emulators can be compared
+/// as long as they can be massaged into this shape. Fields missing from either side of a
+/// comparison are ignored.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub cycle: Option<u64>,
+    pub program_counter: Addr,
+    pub opcode: u16,
+    pub registers: [Option<u8>; 16],
+    pub address_register: Option<u16>,
+    pub stack_depth: Option<u8>,
+    pub delay_timer: Option<u8>,
+    pub sound_timer: Option<u8>,
+    pub writes: Vec<(Addr, u8)>,
+}
+
+impl TraceRecord {
+    /// Captures the state of an emulator before it executes its next instruction.
+    pub fn capture(emulator: &Emulator) -> Self {
+        let pc = emulator.program_counter();
+        let memory = emulator.memory();
+        let high = memory.get(pc as usize).copied().unwrap_or(0);
+        let low = memory.get(pc as usize + 1).copied().unwrap_or(0);
+
+        TraceRecord {
+            cycle: Some(emulator.cycles()),
+            program_counter: pc,
+            opcode: u16::from_be_bytes([high, low]),
+            registers: emulator.registers().map(Some),
+            address_register: Some(emulator.address_register()),
+            stack_depth: Some(emulator.stack_depth() as u8),
+            delay_timer: Some(emulator.delay_timer()),
+            sound_timer: Some(emulator.sound_timer()),
+            writes: Vec::new(),
+        }
+    }
+
+    /// Records the memory writes made by `opcode`, which must be the instruction this
+    /// record was captured before, reading the written values back out of `emulator`.
+    pub fn record_writes(&mut self, opcode: Opcode, emulator: &Emulator) {
+        let count = match opcode {
+            Opcode::LdB(_) => 3,
+            Opcode::Dump(r) => r.0.as_usize() + 1,
+            _ => return,
+        };
+
+        let start = self.address_register.unwrap_or(0);
+        let memory = emulator.memory();
+        for addr in start..start + count as u16 {
+            if let Some(&value) = memory.get(addr as usize) {
+                self.writes.push((addr, value));
+            }
+        }
+    }
+
+    /// Parses a record from a single line of a trace.
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut record = TraceRecord::default();
+        let mut has_pc = false;
+        let mut has_opcode = false;
+
+        for token in line.split_whitespace() {
+            let (key, value) = token
+                .split_once('=')
+                .ok_or_else(|| format!("expected KEY=VALUE, found '{}'", token))?;
+
+            let key = key.to_ascii_uppercase();
+            let parse_err = |_| format!("invalid value for {}: '{}'", key, value);
+            match key.as_str() {
+                "CYC" => record.cycle = Some(u64::from_str_radix(value, 16).map_err(parse_err)?),
+                "PC" => {
+                    record.program_counter = u16::from_str_radix(value, 16).map_err(parse_err)?;
+                    has_pc = true;
+                }
+
+                "OP" => {
+                    record.opcode = u16::from_str_radix(value, 16).map_err(parse_err)?;
+                    has_opcode = true;
+                }
+
+                "I" => {
+                    record.address_register =
+                        Some(u16::from_str_radix(value, 16).map_err(parse_err)?)
+                }
+
+                "SP" => {
+                    record.stack_depth = Some(u8::from_str_radix(value, 16).map_err(parse_err)?)
+                }
+                "DT" => {
+                    record.delay_timer = Some(u8::from_str_radix(value, 16).map_err(parse_err)?)
+                }
+                "ST" => {
+                    record.sound_timer = Some(u8::from_str_radix(value, 16).map_err(parse_err)?)
+                }
+
+                _ if key.len() == 2 && key.starts_with('V') => {
+                    let index = usize::from_str_radix(&key[1..], 16)
+                        .map_err(|_| format!("unknown register '{}'", key))?;
+
+                    record.registers[index] =
+                        Some(u8::from_str_radix(value, 16).map_err(parse_err)?);
+                }
+
+                _ if key.starts_with('[') && key.ends_with(']') => {
+                    let addr = u16::from_str_radix(&key[1..key.len() - 1], 16)
+                        .map_err(|_| format!("invalid address '{}'", key))?;
+
+                    let value = u8::from_str_radix(value, 16).map_err(parse_err)?;
+                    record.writes.push((addr, value));
+                }
+
+                // Unknown keys are skipped so that traces may carry extra information.
+                _ => {}
+            }
+        }
+
+        if !has_pc || !has_opcode {
+            return Err(String::from("record must contain both PC and OP"));
+        }
+
+        Ok(record)
+    }
+}
+
+impl Display for TraceRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(cycle) = self.cycle {
+            write!(f, "CYC={:x} ", cycle)?;
+        }
+
+        write!(f, "PC={:04X} OP={:04X}", self.program_counter, self.opcode)?;
+        for (i, value) in self.registers.iter().enumerate() {
+            if let Some(value) = value {
+                write!(f, " V{:X}={:02X}", i, value)?;
+            }
+        }
+
+        if let Some(i) = self.address_register {
+            write!(f, " I={:04X}", i)?;
+        }
+
+        if let Some(sp) = self.stack_depth {
+            write!(f, " SP={:X}", sp)?;
+        }
+
+        if let Some(dt) = self.delay_timer {
+            write!(f, " DT={:02X}", dt)?;
+        }
+
+        if let Some(st) = self.sound_timer {
+            write!(f, " ST={:02X}", st)?;
+        }
+
+        for (addr, value) in &self.writes {
+            write!(f, " [{:04X}]={:02X}", addr, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum TraceError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl Display for TraceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceError::Io(err) => write!(f, "{}", err),
+            TraceError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+impl From<io::Error> for TraceError {
+    fn from(err: io::Error) -> Self {
+        TraceError::Io(err)
+    }
+}
+
+/// [Trace] is a parsed execution trace. Each record remembers the line it came from so
+/// that divergences can be located in the original files.
+pub struct Trace {
+    records: Vec<(usize, TraceRecord)>,
+}
+
+impl Trace {
+    /// Reads a trace from a reader. Blank lines and lines starting with `#` are ignored.
+    pub fn read<R: BufRead>(r: R) -> Result<Self, TraceError> {
+        let mut records = Vec::new();
+        for (i, line) in r.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let record = TraceRecord::parse(line).map_err(|message| TraceError::Parse {
+                line: i + 1,
+                message,
+            })?;
+
+            records.push((i + 1, record));
+        }
+
+        Ok(Trace { records })
+    }
+
+    /// Returns the number of records in the trace.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Returns true if the trace contains no records.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    fn record(&self, index: usize) -> &TraceRecord {
+        &self.records[index].1
+    }
+
+    fn line(&self, index: usize) -> Option<usize> {
+        self.records.get(index).map(|(line, _)| *line)
+    }
+
+    fn has_writes(&self) -> bool {
+        self.records.iter().any(|(_, r)| !r.writes.is_empty())
+    }
+}
+
+/// [Difference] is a single field which differs between two trace records.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Difference {
+    pub field: String,
+    pub a: String,
+    pub b: String,
+}
+
+/// [Divergence] describes the first point at which two traces disagree.
+#[derive(Debug)]
+pub struct Divergence {
+    /// Index into the first trace of the diverging record.
+    pub index_a: usize,
+
+    /// Index into the second trace of the diverging record.
+    pub index_b: usize,
+
+    /// Number of records which matched before the divergence.
+    pub matched: usize,
+
+    pub differences: Vec<Difference>,
+}
+
+/// Finds the first pair of records at which two traces can be lined up. Traces which
+/// both carry cycle counts are aligned on the first shared cycle, otherwise on the first
+/// record whose PC and opcode appear in the other trace.
+pub fn align(a: &Trace, b: &Trace) -> Option<(usize, usize)> {
+    let cycles_a = a.records.iter().map(|(_, r)| r.cycle);
+    let cycles_b = b.records.iter().map(|(_, r)| r.cycle);
+    if cycles_a
+        .clone()
+        .chain(cycles_b.clone())
+        .all(|c| c.is_some())
+    {
+        for (i, cycle) in cycles_a.enumerate() {
+            if let Some(j) = cycles_b.clone().position(|c| c == cycle) {
+                return Some((i, j));
+            }
+        }
+
+        return None;
+    }
+
+    let same_location = |x: &TraceRecord, y: &TraceRecord| {
+        x.program_counter == y.program_counter && x.opcode == y.opcode
+    };
+
+    for i in 0..a.len() {
+        if let Some(j) = (0..b.len()).find(|&j| same_location(a.record(i), b.record(j))) {
+            return Some((i, j));
+        }
+    }
+
+    None
+}
+
+/// Compares two traces returning the first [Divergence] between them, if any. Traces
+/// are compared in lockstep starting from the point returned by [align]. A trace which
+/// ends before the other is reported as diverging where it runs out of records, with a
+/// `length` difference giving the number of records left in each.
+pub fn diff(a: &Trace, b: &Trace) -> Option<Divergence> {
+    let (start_a, start_b) = align(a, b).unwrap_or((0, 0));
+
+    let compare_writes = a.has_writes() && b.has_writes();
+    let steps = (a.len() - start_a).min(b.len() - start_b);
+    for step in 0..steps {
+        let (index_a, index_b) = (start_a + step, start_b + step);
+        let differences = compare(a.record(index_a), b.record(index_b), compare_writes);
+        if !differences.is_empty() {
+            return Some(Divergence {
+                index_a,
+                index_b,
+                matched: step,
+                differences,
+            });
+        }
+    }
+
+    let (rest_a, rest_b) = (a.len() - start_a, b.len() - start_b);
+    if rest_a != rest_b {
+        return Some(Divergence {
+            index_a: start_a + steps,
+            index_b: start_b + steps,
+            matched: steps,
+            differences: vec![Difference {
+                field: String::from("length"),
+                a: format!("{} records", rest_a),
+                b: format!("{} records", rest_b),
+            }],
+        });
+    }
+
+    None
+}
+
+fn compare(a: &TraceRecord, b: &TraceRecord, compare_writes: bool) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    let mut check = |field: String, x: Option<String>, y: Option<String>| {
+        if let (Some(x), Some(y)) = (x, y) {
+            if x != y {
+                differences.push(Difference { field, a: x, b: y });
+            }
+        }
+    };
+
+    let hex16 = |v: u16| format!("0x{:03X}", v);
+    let hex8 = |v: u8| format!("0x{:02X}", v);
+
+    check(
+        String::from("PC"),
+        Some(hex16(a.program_counter)),
+        Some(hex16(b.program_counter)),
+    );
+
+    check(
+        String::from("OP"),
+        Some(format!("{:04X}", a.opcode)),
+        Some(format!("{:04X}", b.opcode)),
+    );
+
+    for i in 0..16 {
+        check(
+            format!("V{:X}", i),
+            a.registers[i].map(hex8),
+            b.registers[i].map(hex8),
+        );
+    }
+
+    check(
+        String::from("I"),
+        a.address_register.map(hex16),
+        b.address_register.map(hex16),
+    );
+
+    check(
+        String::from("SP"),
+        a.stack_depth.map(|v| v.to_string()),
+        b.stack_depth.map(|v| v.to_string()),
+    );
+
+    check(
+        String::from("DT"),
+        a.delay_timer.map(hex8),
+        b.delay_timer.map(hex8),
+    );
+    check(
+        String::from("ST"),
+        a.sound_timer.map(hex8),
+        b.sound_timer.map(hex8),
+    );
+
+    if compare_writes {
+        let lookup = |writes: &[(Addr, u8)], addr| {
+            writes
+                .iter()
+                .find(|(a, _)| *a == addr)
+                .map(|(_, v)| hex8(*v))
+                .unwrap_or_else(|| String::from("--"))
+        };
+
+        let mut addresses: Vec<Addr> = a.writes.iter().chain(&b.writes).map(|(a, _)| *a).collect();
+        addresses.sort_unstable();
+        addresses.dedup();
+        for addr in addresses {
+            check(
+                format!("[0x{:03X}]", addr),
+                Some(lookup(&a.writes, addr)),
+                Some(lookup(&b.writes, addr)),
+            );
+        }
+    }
+
+    differences
+}
+
+/// Writes a human readable report of a [Divergence] including up to `context` matching
+/// records leading up to it, the diverging records of both traces and up to `context`
+/// records following them in each trace, labelled `a` or `b`.
+pub fn write_report<W: io::Write>(
+    a: &Trace,
+    b: &Trace,
+    divergence: &Divergence,
+    context: usize,
+    w: &mut W,
+) -> io::Result<()> {
+    let line = |trace: &Trace, index| match trace.line(index) {
+        Some(line) => format!("line {}", line),
+        None => String::from("end of trace"),
+    };
+
+    writeln!(
+        w,
+        "Traces diverge after {} matching instructions (a: {}, b: {})",
+        divergence.matched,
+        line(a, divergence.index_a),
+        line(b, divergence.index_b),
+    )?;
+
+    writeln!(w)?;
+    let before = context.min(divergence.matched);
+    for offset in (1..=before).rev() {
+        write_context_line(w, " ", a.records.get(divergence.index_a - offset))?;
+    }
+
+    write_context_line(w, "a", a.records.get(divergence.index_a))?;
+    write_context_line(w, "b", b.records.get(divergence.index_b))?;
+    for (marker, trace, index) in [("a", a, divergence.index_a), ("b", b, divergence.index_b)] {
+        let after = trace.records.iter().skip(index + 1).take(context);
+        for record in after {
+            write_context_line(w, marker, Some(record))?;
+        }
+    }
+
+    writeln!(w)?;
+    writeln!(w, "{:<8} {:<8} b", "field", "a")?;
+    for difference in &divergence.differences {
+        writeln!(
+            w,
+            "{:<8} {:<8} {}",
+            difference.field, difference.a, difference.b
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_context_line<W: io::Write>(
+    w: &mut W,
+    marker: &str,
+    record: Option<&(usize, TraceRecord)>,
+) -> io::Result<()> {
+    let record = match record {
+        Some((_, record)) => record,
+        None => return writeln!(w, "{} (end of trace)", marker),
+    };
+
+    let text = match Opcode::decode(&record.opcode.to_be_bytes()) {
+        Some(opcode) => format!("{}", opcode),
+        None => String::from("--"),
+    };
+
+    writeln!(
+        w,
+        "{} {:03X}   {:04X}    {}",
+        marker, record.program_counter, record.opcode, text
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn trace(text: &str) -> Trace {
+        Trace::read(text.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn record_round_trip() {
+        let line = "CYC=1a PC=0204 OP=8126 V0=00 V1=03 I=0300 SP=1 DT=00 ST=00 [0300]=7F";
+        let record = TraceRecord::parse(line).unwrap();
+        assert_eq!(record.cycle, Some(0x1a));
+        assert_eq!(record.registers[1], Some(0x03));
+        assert_eq!(record.registers[2], None);
+        assert_eq!(record.writes, vec![(0x300, 0x7F)]);
+        assert_eq!(TraceRecord::parse(&record.to_string()).unwrap(), record);
+    }
+
+    #[test]
+    fn diff_reports_first_divergence() {
+        let a = trace("PC=0200 OP=6003 V0=00\nPC=0202 OP=8006 V0=03\nPC=0204 OP=1204 V0=01 VF=01");
+        let b = trace(
+            "# reference\nPC=0200 OP=6003\nPC=0202 OP=8006 V0=03\nPC=0204 OP=1204 V0=01 VF=00",
+        );
+
+        let divergence = diff(&a, &b).unwrap();
+        assert_eq!(divergence.matched, 2);
+        assert_eq!(b.line(divergence.index_b), Some(4));
+        assert_eq!(
+            divergence.differences,
+            vec![Difference {
+                field: String::from("VF"),
+                a: String::from("0x01"),
+                b: String::from("0x00"),
+            }]
+        );
+    }
+
+    #[test]
+    fn diff_aligns_on_cycle() {
+        let a = trace("CYC=0 PC=0200 OP=00E0\nCYC=1 PC=0202 OP=1202");
+        let b = trace("CYC=1 PC=0202 OP=1202");
+        assert!(diff(&a, &b).is_none());
+    }
+
+    #[test]
+    fn diff_reports_truncated_trace() {
+        let a = trace("PC=0200 OP=6003\nPC=0202 OP=1202\nPC=0202 OP=1202");
+        let b = trace("PC=0200 OP=6003\nPC=0202 OP=1202");
+
+        let divergence = diff(&a, &b).unwrap();
+        assert_eq!(divergence.matched, 2);
+        assert_eq!((divergence.index_a, divergence.index_b), (2, 2));
+        assert_eq!(divergence.differences[0].field, "length");
+
+        let mut out = Vec::new();
+        write_report(&a, &b, &divergence, 1, &mut out).unwrap();
+        let report = String::from_utf8(out).unwrap();
+        assert!(report.contains("(a: line 3, b: end of trace)"));
+        assert!(report.contains("b (end of trace)"));
+
+        // Records following the divergence are shown from both traces.
+        let a = trace("PC=0200 OP=6003\nPC=0202 OP=6101\nPC=0204 OP=6202\nPC=0206 OP=6303");
+        let b = trace("PC=0200 OP=6003\nPC=0202 OP=6105\nPC=0204 OP=6206");
+        let divergence = diff(&a, &b).unwrap();
+
+        let mut out = Vec::new();
+        write_report(&a, &b, &divergence, 2, &mut out).unwrap();
+        let report = String::from_utf8(out).unwrap();
+        let expected = [
+            "  200   6003    LD   V0, 0x03",
+            "a 202   6101    LD   V1, 0x01",
+            "b 202   6105    LD   V1, 0x05",
+            "a 204   6202    LD   V2, 0x02",
+            "a 206   6303    LD   V3, 0x03",
+            "b 204   6206    LD   V2, 0x06",
+        ];
+        assert!(report.contains(&expected.join("\n")), "{}", report);
+
+        assert!(diff(&a, &trace("")).is_some());
+        assert!(diff(&trace(""), &trace("")).is_none());
+    }
+}
@@ -0,0 +1,65 @@
+#![cfg(feature = "std")]
+
+use std::{fs, path::PathBuf, process::Command};
+
+use chip8::movie::Movie;
+
+/// Writes a program which jumps to itself forever to a directory of its own, returning
+/// the directory.
+fn rom_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("chip8-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("loop.ch8"), [0x12, 0x00]).unwrap();
+    dir
+}
+
+#[test]
+fn headless_screenshot() {
+    let dir = rom_dir("headless");
+    let screenshot = dir.join("out.png");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_chip8"))
+        .args(["run", "--headless", "--frames", "5", "--screenshot"])
+        .arg(&screenshot)
+        .arg(dir.join("loop.ch8"))
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+    assert_eq!(&fs::read(&screenshot).unwrap()[1..4], b"PNG");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn headless_requires_a_limit() {
+    let dir = rom_dir("unlimited");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_chip8"))
+        .args(["run", "--headless"])
+        .arg(dir.join("loop.ch8"))
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--cycles or --frames"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn movie_leaves_out_partial_frame() {
+    let dir = rom_dir("movie");
+    let movie = dir.join("loop.movie");
+
+    // Ten instructions run per frame, so the last frame is cut short.
+    let status = Command::new(env!("CARGO_BIN_EXE_chip8"))
+        .args(["run", "--headless", "--cycles", "25", "--record-movie"])
+        .arg(&movie)
+        .arg(dir.join("loop.ch8"))
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+    let movie = Movie::read(fs::read(&movie).unwrap().as_slice()).unwrap();
+    assert_eq!(movie.frames, 2);
+    fs::remove_dir_all(dir).unwrap();
+}